@@ -1,17 +1,24 @@
-fn download_7zr() {
-    let out_path =
-        std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("../../../7zr.exe");
+fn download_7zr() -> Result<(), Box<dyn std::error::Error>> {
+    let out_path = std::path::PathBuf::from(std::env::var("OUT_DIR")?).join("../../../7zr.exe");
 
     if out_path.exists() {
-        return;
+        return Ok(());
     }
 
     let url = "https://www.7-zip.org/a/7zr.exe";
-    let response = ureq::get(url).call().unwrap();
-    let mut file = std::fs::File::create(&out_path).unwrap();
-    std::io::copy(&mut response.into_reader(), &mut file).unwrap();
+    let response = ureq::get(url).call()?;
+    let mut file = std::fs::File::create(&out_path)?;
+    std::io::copy(&mut response.into_reader(), &mut file)?;
+
+    Ok(())
 }
 
 fn main() {
-    download_7zr();
+    // 7zr.exe is only needed by the optional external 7z backend.
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("windows") {
+        return;
+    }
+    if let Err(e) = download_7zr() {
+        println!("cargo:warning=Failed to download 7zr.exe: {}", e);
+    }
 }
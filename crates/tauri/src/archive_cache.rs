@@ -0,0 +1,205 @@
+use crate::coeiroink_scraping::Edition;
+use crate::coeiroink_version::CoeiroinkVersion;
+use anyhow::Result;
+use tauri::Manager;
+use tracing::{info, warn};
+
+static DEFAULT_LIMIT_BYTES: u64 = 16 * 1024 * 1024 * 1024;
+
+static ARCHIVE_CACHE_LOCK: once_cell::sync::Lazy<tokio::sync::Mutex<()>> =
+    once_cell::sync::Lazy::new(|| tokio::sync::Mutex::new(()));
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedArchive {
+    pub version: CoeiroinkVersion,
+    pub edition: Edition,
+    pub file_name: String,
+    pub size: u64,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    pub stored_at: u64,
+    pub last_used: u64,
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn archives_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf> {
+    Ok(app_handle.path().app_cache_dir()?.join("archives"))
+}
+
+fn cache_limit(app_handle: &tauri::AppHandle) -> u64 {
+    crate::settings::get(app_handle, "archive_cache_limit")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_LIMIT_BYTES)
+}
+
+fn archive_file_name(version: &CoeiroinkVersion, edition: &Edition) -> String {
+    format!("coeiroink-{}-{}.zip", version.normalized(), edition).to_lowercase()
+}
+
+async fn read_index(archives_dir: &std::path::Path) -> Vec<CachedArchive> {
+    let index_path = archives_dir.join("index.json");
+    if !index_path.exists() {
+        return vec![];
+    }
+
+    let index = fs_err::tokio::read_to_string(&index_path)
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(serde_json::from_str::<Vec<CachedArchive>>(&json)?));
+    let index = match index {
+        Ok(index) => index,
+        Err(e) => {
+            warn!("Failed to read archive cache index: {:?}", e);
+            return vec![];
+        }
+    };
+
+    // Drop entries whose file was deleted behind our back, and any whose name was not made by
+    // `archive_file_name`, so that the index cannot point outside the cache.
+    index
+        .into_iter()
+        .filter(|a| a.file_name == archive_file_name(&a.version, &a.edition))
+        .filter(|a| archives_dir.join(&a.file_name).exists())
+        .collect()
+}
+
+async fn write_index(archives_dir: &std::path::Path, index: &[CachedArchive]) -> Result<()> {
+    fs_err::tokio::create_dir_all(archives_dir).await?;
+    fs_err::tokio::write(archives_dir.join("index.json"), serde_json::to_vec(index)?).await?;
+
+    Ok(())
+}
+
+async fn remove_archive(archives_dir: &std::path::Path, archive: &CachedArchive) {
+    info!(
+        "Removing cached archive: {} {} ({} bytes)",
+        archive.version, archive.edition, archive.size
+    );
+    if let Err(e) = fs_err::tokio::remove_file(archives_dir.join(&archive.file_name)).await {
+        warn!("Failed to remove cached archive: {:?}", e);
+    }
+}
+
+/// Returns the path and entry of the cached archive for `version` and `edition`, if any.
+pub async fn find(
+    app_handle: &tauri::AppHandle,
+    version: &CoeiroinkVersion,
+    edition: &Edition,
+) -> Result<Option<(std::path::PathBuf, CachedArchive)>> {
+    let _lock = ARCHIVE_CACHE_LOCK.lock().await;
+    let archives_dir = archives_dir(app_handle)?;
+    let mut index = read_index(&archives_dir).await;
+
+    let Some(archive) = index
+        .iter_mut()
+        .find(|a| a.version == *version && a.edition == *edition)
+    else {
+        return Ok(None);
+    };
+    archive.last_used = now();
+    let found = (
+        archives_dir.join(archive_file_name(version, edition)),
+        archive.clone(),
+    );
+    write_index(&archives_dir, &index).await?;
+
+    Ok(Some(found))
+}
+
+/// Moves a downloaded archive into the cache and evicts the least recently used archives until the
+/// cache fits in `archive_cache_limit` again. The new archive itself is never evicted.
+pub async fn store(
+    app_handle: &tauri::AppHandle,
+    version: &CoeiroinkVersion,
+    edition: &Edition,
+    downloaded: &std::path::Path,
+    sha256: &str,
+) -> Result<std::path::PathBuf> {
+    let _lock = ARCHIVE_CACHE_LOCK.lock().await;
+    let archives_dir = archives_dir(app_handle)?;
+    fs_err::tokio::create_dir_all(&archives_dir).await?;
+    let mut index = read_index(&archives_dir).await;
+
+    let file_name = archive_file_name(version, edition);
+    let path = archives_dir.join(&file_name);
+    info!("Caching archive: {:?} -> {:?}", downloaded, path);
+    fs_err::tokio::rename(downloaded, &path).await?;
+
+    index.retain(|a| !(a.version == *version && a.edition == *edition));
+    index.push(CachedArchive {
+        version: version.clone(),
+        edition: edition.clone(),
+        file_name,
+        size: fs_err::tokio::metadata(&path).await?.len(),
+        sha256: Some(sha256.to_string()),
+        stored_at: now(),
+        last_used: now(),
+    });
+
+    let limit = cache_limit(app_handle);
+    index.sort_by_key(|a| std::cmp::Reverse(a.last_used));
+    // `last_used` is in seconds, so the new archive may tie with another; exempt it by identity.
+    let is_new = |a: &CachedArchive| a.version == *version && a.edition == *edition;
+    let mut total = index
+        .iter()
+        .filter(|a| is_new(a))
+        .map(|a| a.size)
+        .sum::<u64>();
+    let mut kept = vec![];
+    for archive in index {
+        if !is_new(&archive) {
+            if total + archive.size > limit {
+                remove_archive(&archives_dir, &archive).await;
+                continue;
+            }
+            total += archive.size;
+        }
+        kept.push(archive);
+    }
+    write_index(&archives_dir, &kept).await?;
+
+    Ok(path)
+}
+
+pub async fn list(app_handle: &tauri::AppHandle) -> Result<Vec<CachedArchive>> {
+    let _lock = ARCHIVE_CACHE_LOCK.lock().await;
+    let archives_dir = archives_dir(app_handle)?;
+
+    Ok(read_index(&archives_dir).await)
+}
+
+/// Removes the matching archives (all of them when both filters are `None`) and returns the number
+/// of bytes freed. Jobs use cached archives without holding the cache lock, so this must run as a
+/// job itself, where it cannot overlap one.
+pub async fn purge(
+    app_handle: &tauri::AppHandle,
+    version: Option<&CoeiroinkVersion>,
+    edition: Option<&Edition>,
+) -> Result<u64> {
+    let _lock = ARCHIVE_CACHE_LOCK.lock().await;
+    let archives_dir = archives_dir(app_handle)?;
+    let index = read_index(&archives_dir).await;
+
+    let mut freed = 0;
+    let mut kept = vec![];
+    for archive in index {
+        let matches = version.is_none_or(|v| archive.version == *v)
+            && edition.is_none_or(|e| archive.edition == *e);
+        if !matches {
+            kept.push(archive);
+            continue;
+        }
+        remove_archive(&archives_dir, &archive).await;
+        freed += archive.size;
+    }
+    write_index(&archives_dir, &kept).await?;
+
+    Ok(freed)
+}
@@ -0,0 +1,128 @@
+use crate::catalog_source::{CatalogFetch, CatalogSource as _, CatalogSourceConfig, Validators};
+use crate::coeiroink_scraping::Catalog;
+use anyhow::Result;
+use tauri::Manager;
+use tracing::{info, warn};
+
+static DEFAULT_TTL_SECS: u64 = 60 * 60;
+
+static CATALOG_CACHE_LOCK: once_cell::sync::Lazy<tokio::sync::Mutex<()>> =
+    once_cell::sync::Lazy::new(|| tokio::sync::Mutex::new(()));
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedCatalog {
+    source: CatalogSourceConfig,
+    fetched_at: u64,
+    validators: Validators,
+    #[serde(flatten)]
+    catalog: Catalog,
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn cache_path(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf> {
+    Ok(app_handle.path().app_data_dir()?.join("catalog_cache.json"))
+}
+
+fn cache_ttl(app_handle: &tauri::AppHandle) -> u64 {
+    crate::settings::get(app_handle, "catalog_cache_ttl")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_TTL_SECS)
+}
+
+async fn read_cache(path: &std::path::Path) -> Result<Option<CachedCatalog>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json = fs_err::tokio::read_to_string(path).await?;
+
+    Ok(Some(serde_json::from_str(&json)?))
+}
+
+async fn write_cache(path: &std::path::Path, cache: &CachedCatalog) -> Result<()> {
+    fs_err::tokio::create_dir_all(path.parent().unwrap()).await?;
+    fs_err::tokio::write(path, serde_json::to_vec(cache)?).await?;
+
+    Ok(())
+}
+
+/// Returns the catalog from the on-disk cache, fetching it from the configured source when the
+/// cache is stale (or `force_refresh` is set). Falls back to the last good catalog if the fetch
+/// fails.
+pub async fn load_catalog(app_handle: &tauri::AppHandle, force_refresh: bool) -> Result<Catalog> {
+    let _lock = CATALOG_CACHE_LOCK.lock().await;
+
+    let source = crate::catalog_source::configured_source(app_handle)?;
+    let path = cache_path(app_handle)?;
+
+    let cache = read_cache(&path)
+        .await
+        .inspect_err(|e| warn!("Failed to read catalog cache: {:?}", e))
+        .ok()
+        .flatten()
+        .filter(|cache| cache.source == source);
+
+    if let Some(cache) = &cache {
+        let age = now().saturating_sub(cache.fetched_at);
+        if !force_refresh && age < cache_ttl(app_handle) {
+            info!("Using cached catalog ({}s old)", age);
+            return Ok(cache.catalog.clone());
+        }
+    }
+
+    info!("Fetching downloads from {:?}", source);
+    let validators = cache
+        .as_ref()
+        .map(|cache| cache.validators.clone())
+        .unwrap_or_default();
+
+    let client = crate::http_client::client(app_handle)?;
+    let fetched = crate::retry::retry(
+        &crate::retry::RetryPolicy::configured(app_handle),
+        "Fetching the catalog",
+        || source.fetch_catalog(&client, &validators),
+    )
+    .await;
+    let new_cache = match fetched {
+        Ok(CatalogFetch::NotModified) => {
+            let Some(cache) = cache else {
+                anyhow::bail!("Server reported not modified without a cached catalog");
+            };
+            CachedCatalog {
+                fetched_at: now(),
+                ..cache
+            }
+        }
+        Ok(CatalogFetch::Modified {
+            catalog,
+            validators,
+        }) => CachedCatalog {
+            source,
+            fetched_at: now(),
+            validators,
+            catalog,
+        },
+        Err(e) => {
+            let Some(cache) = cache else {
+                return Err(e);
+            };
+            warn!(
+                "Failed to fetch catalog, using the last good catalog: {:?}",
+                e
+            );
+            return Ok(cache.catalog);
+        }
+    };
+
+    if let Err(e) = write_cache(&path, &new_cache).await {
+        warn!("Failed to write catalog cache: {:?}", e);
+    }
+
+    Ok(new_cache.catalog)
+}
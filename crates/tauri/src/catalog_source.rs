@@ -0,0 +1,367 @@
+use crate::coeiroink_scraping::Catalog;
+use crate::http_client::HttpClient;
+use crate::release_manifest::ManifestLocation;
+use anyhow::Result;
+use tracing::info;
+
+pub static DOWNLOAD_PAGE_URL: &str = "https://coeiroink.com/download";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+pub enum CatalogFetch {
+    NotModified,
+    Modified {
+        catalog: Catalog,
+        validators: Validators,
+    },
+}
+
+pub trait CatalogSource {
+    async fn fetch_catalog(
+        &self,
+        client: &HttpClient,
+        validators: &Validators,
+    ) -> Result<CatalogFetch>;
+}
+
+pub struct LiveSite {
+    pub url: String,
+}
+
+impl Default for LiveSite {
+    fn default() -> Self {
+        Self {
+            url: DOWNLOAD_PAGE_URL.to_string(),
+        }
+    }
+}
+
+impl CatalogSource for LiveSite {
+    async fn fetch_catalog(
+        &self,
+        client: &HttpClient,
+        validators: &Validators,
+    ) -> Result<CatalogFetch> {
+        info!("Fetching download page: {}", self.url);
+        let mut request = client.client.get(&self.url);
+        if let Some(etag) = &validators.etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+
+        let response = request.send().await?.error_for_status()?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            info!("Download page not modified");
+            return Ok(CatalogFetch::NotModified);
+        }
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        let validators = Validators {
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        };
+
+        let html = response.text().await?;
+
+        Ok(CatalogFetch::Modified {
+            catalog: crate::coeiroink_scraping::parse_catalog(&html)?,
+            validators,
+        })
+    }
+}
+
+/// A saved copy of the download page, e.g. `fixtures/download_page.html`.
+pub struct HtmlFile {
+    pub path: std::path::PathBuf,
+}
+
+impl CatalogSource for HtmlFile {
+    async fn fetch_catalog(
+        &self,
+        _client: &HttpClient,
+        _validators: &Validators,
+    ) -> Result<CatalogFetch> {
+        info!("Reading download page from file: {:?}", self.path);
+        let html = fs_err::tokio::read_to_string(&self.path).await?;
+
+        Ok(CatalogFetch::Modified {
+            catalog: crate::coeiroink_scraping::parse_catalog(&html)?,
+            validators: Validators::default(),
+        })
+    }
+}
+
+/// A self-hosted release manifest, see `release_manifest`.
+pub struct JsonManifest {
+    pub location: ManifestLocation,
+}
+
+impl CatalogSource for JsonManifest {
+    async fn fetch_catalog(
+        &self,
+        client: &HttpClient,
+        _validators: &Validators,
+    ) -> Result<CatalogFetch> {
+        let manifest = crate::release_manifest::load_manifest(client, &self.location).await?;
+
+        Ok(CatalogFetch::Modified {
+            catalog: manifest.to_catalog(),
+            validators: Validators::default(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum CatalogSourceConfig {
+    #[default]
+    LiveSite,
+    HtmlFile {
+        path: std::path::PathBuf,
+    },
+    JsonManifest {
+        #[serde(flatten)]
+        location: ManifestLocation,
+    },
+}
+
+impl CatalogSource for CatalogSourceConfig {
+    async fn fetch_catalog(
+        &self,
+        client: &HttpClient,
+        validators: &Validators,
+    ) -> Result<CatalogFetch> {
+        match self {
+            CatalogSourceConfig::LiveSite => {
+                LiveSite::default().fetch_catalog(client, validators).await
+            }
+            CatalogSourceConfig::HtmlFile { path } => {
+                HtmlFile { path: path.clone() }
+                    .fetch_catalog(client, validators)
+                    .await
+            }
+            CatalogSourceConfig::JsonManifest { location } => {
+                JsonManifest {
+                    location: location.clone(),
+                }
+                .fetch_catalog(client, validators)
+                .await
+            }
+        }
+    }
+}
+
+pub fn configured_source(app_handle: &tauri::AppHandle) -> Result<CatalogSourceConfig> {
+    let Some(catalog_source) = crate::settings::get(app_handle, "catalog_source") else {
+        return Ok(CatalogSourceConfig::default());
+    };
+
+    Ok(serde_json::from_value(catalog_source)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coeiroink_scraping::{DownloadInfo, Edition, Os};
+    use crate::http_client::NetworkSettings;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name)
+    }
+
+    fn fetch(source: impl CatalogSource) -> Catalog {
+        let client = HttpClient::build(&NetworkSettings::default()).unwrap();
+        let fetch =
+            tauri::async_runtime::block_on(source.fetch_catalog(&client, &Validators::default()))
+                .unwrap();
+        let CatalogFetch::Modified { catalog, .. } = fetch else {
+            panic!("Expected a catalog");
+        };
+
+        catalog
+    }
+
+    fn download(
+        edition: Edition,
+        os: Os,
+        version: &str,
+        link: &str,
+        release_date: Option<&str>,
+        size: Option<u64>,
+    ) -> DownloadInfo {
+        DownloadInfo {
+            edition,
+            os,
+            version: version.parse().unwrap(),
+            link: link.to_string(),
+            mirrors: vec![],
+            release_date: release_date.map(str::to_string),
+            size,
+            sha256: None,
+        }
+    }
+
+    #[test]
+    fn parses_download_page_fixture() {
+        let catalog = fetch(HtmlFile {
+            path: fixture("download_page.html"),
+        });
+
+        let dropbox = |id: char, name: &str| {
+            let id = id.to_string();
+            format!(
+                "https://www.dropbox.com/scl/fi/{}/{}.zip?rlkey={}&dl=0",
+                id.repeat(21),
+                name,
+                id.repeat(25)
+            )
+        };
+        let latest = |edition, os, id, name: &str, size| {
+            download(
+                edition,
+                os,
+                "2.3.4",
+                &dropbox(id, name),
+                Some("2024-06-01"),
+                Some(size),
+            )
+        };
+        let old = |edition, os, version, id, name: &str| {
+            download(edition, os, version, &dropbox(id, name), None, None)
+        };
+        assert_eq!(
+            catalog.downloads,
+            vec![
+                latest(
+                    Edition::Cpu,
+                    Os::Windows,
+                    'a',
+                    "COEIROINK-CPU-v.2.3.4-Windows",
+                    1_600_000_000
+                ),
+                latest(
+                    Edition::Gpu,
+                    Os::Windows,
+                    'b',
+                    "COEIROINK-GPU-v.2.3.4-Windows",
+                    3_900_000_000
+                ),
+                latest(
+                    Edition::Cpu,
+                    Os::Mac,
+                    'c',
+                    "COEIROINK-CPU-v.2.3.4-Mac",
+                    1_400_000_000
+                ),
+                old(
+                    Edition::Cpu,
+                    Os::Windows,
+                    "2.3.3",
+                    'd',
+                    "v.2.3.3-windows-cpu"
+                ),
+                old(
+                    Edition::Gpu,
+                    Os::Windows,
+                    "2.3.3",
+                    'e',
+                    "v.2.3.3-windows-gpu"
+                ),
+                old(
+                    Edition::Cpu,
+                    Os::Windows,
+                    "2.2.1",
+                    'f',
+                    "v.2.2.1-windows-cpu"
+                ),
+                old(Edition::Cpu, Os::Mac, "2.2.1", 'g', "v.2.2.1-mac-cpu"),
+                old(
+                    Edition::Cpu,
+                    Os::Windows,
+                    "1.7.3",
+                    'h',
+                    "v.1.7.3-windows-cpu"
+                ),
+                old(
+                    Edition::Gpu,
+                    Os::Windows,
+                    "1.7.3",
+                    'i',
+                    "v.1.7.3-windows-gpu"
+                ),
+            ]
+        );
+
+        let releases = catalog
+            .releases
+            .iter()
+            .map(|r| (r.version.to_string(), r.release_date.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            releases,
+            vec![
+                ("2.3.4".to_string(), Some("2024-06-01")),
+                ("2.3.3".to_string(), Some("2024-04-20")),
+                ("2.2.1".to_string(), Some("2023-12-24")),
+            ]
+        );
+        assert_eq!(
+            catalog.releases[0].notes,
+            "話速を変更したときに音声が途切れる問題を修正\nMYCOEIROINKの読み込みを高速化"
+        );
+    }
+
+    #[test]
+    fn parses_manifest_fixture() {
+        let catalog = fetch(JsonManifest {
+            location: ManifestLocation::Path(fixture("download_manifest.json")),
+        });
+
+        let sha256 = Some("0".repeat(64));
+        assert_eq!(
+            catalog.downloads,
+            vec![
+                DownloadInfo {
+                    sha256: sha256.clone(),
+                    ..download(
+                        Edition::Cpu,
+                        Os::Windows,
+                        "2.3.4",
+                        "https://www.dropbox.com/scl/fi/aaaaaaaaaaaaaaaaaaaaa/COEIROINK-CPU-v.2.3.4-Windows.zip?rlkey=aaaaaaaaaaaaaaaaaaaaaaaaa&dl=0",
+                        Some("2024-06-01"),
+                        Some(1_600_000_000),
+                    )
+                },
+                DownloadInfo {
+                    sha256,
+                    ..download(
+                        Edition::Gpu,
+                        Os::Windows,
+                        "2.3.4",
+                        "https://www.dropbox.com/scl/fi/bbbbbbbbbbbbbbbbbbbbb/COEIROINK-GPU-v.2.3.4-Windows.zip?rlkey=bbbbbbbbbbbbbbbbbbbbbbbbb&dl=0",
+                        Some("2024-06-01"),
+                        Some(3_900_000_000),
+                    )
+                },
+            ]
+        );
+
+        assert_eq!(catalog.releases.len(), 1);
+        assert_eq!(catalog.releases[0].version.to_string(), "2.3.4");
+    }
+}
@@ -0,0 +1,125 @@
+use crate::coeiroink_scraping::{DownloadInfo, Edition};
+use crate::coeiroink_version::CoeiroinkVersion;
+use anyhow::Result;
+use sha2::Digest;
+use tauri::Manager;
+use tokio::io::AsyncReadExt;
+use tracing::{info, warn};
+
+#[derive(Debug)]
+pub struct ChecksumMismatch {
+    pub path: std::path::PathBuf,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl std::fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Checksum mismatch for {}: expected sha256 {}, got {}",
+            self.path.display(),
+            self.expected,
+            self.actual
+        )
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+/// An entry of `trusted_hashes.json` in the app data directory, for archives that are not covered
+/// by a release manifest.
+#[derive(Debug, Clone, serde::Deserialize)]
+struct TrustedHash {
+    version: CoeiroinkVersion,
+    edition: Edition,
+    sha256: String,
+}
+
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Feeds the contents of `path` into `hasher`.
+pub async fn hash_into(hasher: &mut sha2::Sha256, path: &std::path::Path) -> Result<()> {
+    let mut file = fs_err::tokio::File::open(path).await?;
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(())
+}
+
+pub async fn hash_file(path: &std::path::Path) -> Result<String> {
+    info!("Hashing {:?}", path);
+    let mut hasher = sha2::Sha256::new();
+    hash_into(&mut hasher, path).await?;
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+async fn trusted_sha256(
+    app_handle: &tauri::AppHandle,
+    version: &CoeiroinkVersion,
+    edition: &Edition,
+) -> Result<Option<String>> {
+    let path = app_handle
+        .path()
+        .app_data_dir()?
+        .join("trusted_hashes.json");
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json = fs_err::tokio::read_to_string(&path).await?;
+    let trusted_hashes: Vec<TrustedHash> = serde_json::from_str(&json)?;
+
+    Ok(trusted_hashes
+        .into_iter()
+        .find(|h| h.version == *version && h.edition == *edition)
+        .map(|h| h.sha256.to_ascii_lowercase()))
+}
+
+/// The hash an archive is expected to have: from the release manifest if it came from one,
+/// otherwise from the local trust database.
+pub async fn known_sha256(
+    app_handle: &tauri::AppHandle,
+    download: Option<&DownloadInfo>,
+    version: &CoeiroinkVersion,
+    edition: &Edition,
+) -> Option<String> {
+    if let Some(sha256) = download.and_then(|d| d.sha256.clone()) {
+        return Some(sha256.to_ascii_lowercase());
+    }
+
+    trusted_sha256(app_handle, version, edition)
+        .await
+        .inspect_err(|e| warn!("Failed to read trusted hashes: {:?}", e))
+        .ok()
+        .flatten()
+}
+
+pub fn verify(
+    path: &std::path::Path,
+    expected: Option<&str>,
+    actual: &str,
+) -> Result<(), ChecksumMismatch> {
+    let Some(expected) = expected else {
+        warn!("No known checksum for {:?} (sha256 {})", path, actual);
+        return Ok(());
+    };
+    if !expected.eq_ignore_ascii_case(actual) {
+        return Err(ChecksumMismatch {
+            path: path.to_owned(),
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        });
+    }
+    info!("Checksum verified: {:?}", path);
+
+    Ok(())
+}
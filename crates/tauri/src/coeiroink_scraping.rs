@@ -1,6 +1,7 @@
+use crate::coeiroink_version::CoeiroinkVersion;
 use anyhow::Result;
 use lazy_regex::regex;
-use tracing::warn;
+use tracing::{info, warn};
 
 #[derive(
     Debug,
@@ -19,7 +20,16 @@ pub enum Edition {
     Gpu,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, strum::EnumString, strum::Display, serde::Serialize)]
+#[derive(
+    Debug,
+    PartialEq,
+    Eq,
+    Clone,
+    strum::EnumString,
+    strum::Display,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 #[serde(rename_all = "lowercase")]
 #[strum(ascii_case_insensitive)]
 pub enum Os {
@@ -27,147 +37,498 @@ pub enum Os {
     Mac,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, serde::Serialize)]
+#[derive(Debug, PartialEq, Eq, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DownloadInfo {
     pub edition: Edition,
     pub os: Os,
-    pub version: String,
+    pub version: CoeiroinkVersion,
     pub link: String,
+    /// Alternative URLs for the same archive, tried in order after `link`.
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+    #[serde(default)]
+    pub release_date: Option<String>,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseNotes {
+    pub version: CoeiroinkVersion,
+    pub release_date: Option<String>,
+    pub notes: String,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Catalog {
+    pub downloads: Vec<DownloadInfo>,
+    #[serde(default)]
+    pub releases: Vec<ReleaseNotes>,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogQuery {
+    pub os: Option<Os>,
+    pub edition: Option<Edition>,
+    pub major_version: Option<u64>,
+}
+
+impl CatalogQuery {
+    /// Builds that CoeiroBottle itself can install.
+    pub fn installable() -> Self {
+        Self {
+            os: Some(Os::Windows),
+            edition: None,
+            major_version: Some(2),
+        }
+    }
+
+    pub fn matches(&self, download: &DownloadInfo) -> bool {
+        if self.os.as_ref().is_some_and(|os| *os != download.os) {
+            return false;
+        }
+        if self
+            .edition
+            .as_ref()
+            .is_some_and(|edition| *edition != download.edition)
+        {
+            return false;
+        }
+        if self
+            .major_version
+            .is_some_and(|major_version| major_version != download.version.major())
+        {
+            return false;
+        }
+
+        true
+    }
+
+    pub fn filter(&self, downloads: Vec<DownloadInfo>) -> Vec<DownloadInfo> {
+        downloads.into_iter().filter(|d| self.matches(d)).collect()
+    }
+}
+
+pub async fn fetch_downloads(app_handle: tauri::AppHandle) -> Result<Vec<DownloadInfo>> {
+    let catalog = crate::catalog_cache::load_catalog(&app_handle, false).await?;
+
+    Ok(sort_downloads(catalog.downloads))
+}
+
+pub async fn refresh_downloads(app_handle: tauri::AppHandle) -> Result<Vec<DownloadInfo>> {
+    let catalog = crate::catalog_cache::load_catalog(&app_handle, true).await?;
+
+    Ok(sort_downloads(catalog.downloads))
 }
 
-#[cached::proc_macro::once(result)]
-pub async fn fetch_downloads() -> Result<Vec<DownloadInfo>> {
-    let coeiroink_download = reqwest::get("https://coeiroink.com/download")
+pub async fn fetch_releases(app_handle: tauri::AppHandle) -> Result<Vec<ReleaseNotes>> {
+    let mut releases = crate::catalog_cache::load_catalog(&app_handle, false)
         .await?
-        .text()
-        .await?;
+        .releases;
+    releases.sort_by(|a, b| b.version.cmp(&a.version));
 
-    let parsed = scraper::Html::parse_document(&coeiroink_download);
-    let selector = scraper::Selector::parse("div.text-center.mt-20.text-xl.font-bold").unwrap();
-    let download_header = parsed
-        .select(&selector)
-        .find(|e| {
-            e.first_child()
-                .map(|e| e.value().as_text().map(|t| t.trim()) == Some("DropBoxからダウンロード"))
-                .unwrap_or(false)
-        })
-        .ok_or_else(|| anyhow::anyhow!("Could not find download header"))?;
+    Ok(releases)
+}
 
-    let table = download_header
-        .next_sibling()
-        .ok_or_else(|| anyhow::anyhow!("Could not find table"))?;
+pub async fn query_downloads(
+    app_handle: tauri::AppHandle,
+    query: &CatalogQuery,
+) -> Result<Vec<DownloadInfo>> {
+    Ok(query.filter(fetch_downloads(app_handle).await?))
+}
 
-    let links = scraper::ElementRef::wrap(table)
-        .ok_or_else(|| anyhow::anyhow!("Could not wrap table"))?
-        .select(&scraper::Selector::parse("a").unwrap())
-        .collect::<Vec<_>>();
+fn sort_downloads(mut downloads: Vec<DownloadInfo>) -> Vec<DownloadInfo> {
+    downloads.sort_by(|a, b| b.version.cmp(&a.version));
 
-    let latest_name_pattern =
-        regex!(r#"COEIROINK-(?P<edition>CPU|GPU)-v\.(?P<version>[0-9.+]+)\((?P<os>Windows|Mac)\)"#);
+    downloads
+}
 
-    let mut downloads = Vec::new();
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum ScrapeStage {
+    #[strum(serialize = "header")]
+    Header,
+    #[strum(serialize = "table")]
+    Table,
+    #[strum(serialize = "link text")]
+    LinkText,
+    #[strum(serialize = "regex capture")]
+    RegexCapture,
+    #[strum(serialize = "link href")]
+    Href,
+}
 
-    for link in links {
-        let text = link
-            .text()
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Could not find text"))?;
+/// Where and why scraping the download page failed.
+#[derive(Debug)]
+pub struct ScrapeError {
+    pub stage: ScrapeStage,
+    pub section: &'static str,
+    pub selector: String,
+    pub snippet: String,
+}
 
-        let Some(captures) = latest_name_pattern.captures(text) else {
-            warn!("Could not find captures for {}", text);
-            continue;
-        };
+impl ScrapeError {
+    fn new(
+        stage: ScrapeStage,
+        section: &'static str,
+        selector: &str,
+        context: Option<scraper::ElementRef>,
+    ) -> Self {
+        Self {
+            stage,
+            section,
+            selector: selector.to_string(),
+            snippet: context.map(|e| snippet(&e.html())).unwrap_or_default(),
+        }
+    }
+}
 
-        let edition = captures["edition"].parse()?;
+impl std::fmt::Display for ScrapeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Could not scrape {} of {} section (selector: `{}`)",
+            self.stage, self.section, self.selector
+        )?;
+        if !self.snippet.is_empty() {
+            write!(f, " near: {}", self.snippet)?;
+        }
+        Ok(())
+    }
+}
 
-        let version = captures["version"].to_string();
+impl std::error::Error for ScrapeError {}
 
-        let os = captures["os"].parse()?;
+fn snippet(html: &str) -> String {
+    const MAX_CHARS: usize = 300;
+    let html = html.split_whitespace().collect::<Vec<_>>().join(" ");
+    if html.chars().count() <= MAX_CHARS {
+        return html;
+    }
+    let mut snippet = html.chars().take(MAX_CHARS).collect::<String>();
+    snippet.push('…');
+    snippet
+}
+
+static LATEST_SECTION: &str = "latest version";
+static OLD_VERSION_SECTION: &str = "old version";
+
+static DOWNLOAD_HEADER_SELECTOR: &str = "div.text-center.mt-20.text-xl.font-bold";
+static OLD_VERSION_HEADER_SELECTOR: &str =
+    r#"div.flex.justify-center.items-center.flex-wrap.lg\:px-5.relative.z-10.md\:pt-40.pt-28"#;
+static LINK_SELECTOR: &str = "a";
 
-        let link = link
-            .value()
-            .attr("href")
-            .ok_or_else(|| anyhow::anyhow!("Could not find link"))?;
+fn latest_name_pattern() -> &'static regex::Regex {
+    regex!(r#"COEIROINK-(?P<edition>CPU|GPU)-v\.(?P<version>[0-9.+]+)\((?P<os>Windows|Mac)\)"#)
+}
+
+fn old_version_name_pattern() -> &'static regex::Regex {
+    regex!(r#"v\.(?P<version>[0-9.]+)-(?P<os>windows|mac)-(?P<edition>cpu|gpu)"#)
+}
 
-        downloads.push(DownloadInfo {
-            edition,
-            os,
-            version,
-            link: link.to_string(),
-        });
+pub fn parse_catalog(html: &str) -> Result<Catalog> {
+    let parsed = scraper::Html::parse_document(html);
+
+    Ok(Catalog {
+        downloads: parse_downloads(&parsed)?,
+        releases: parse_release_notes(&parsed),
+    })
+}
+
+fn parse_downloads(parsed: &scraper::Html) -> Result<Vec<DownloadInfo>> {
+    let structured = parse_latest_section(parsed).and_then(|mut downloads| {
+        downloads.extend(parse_old_version_section(parsed)?);
+        Ok(downloads)
+    });
+
+    match structured {
+        Ok(downloads) => Ok(downloads),
+        Err(e) => {
+            warn!("{}", e);
+            warn!("Falling back to scanning every link on the page");
+            let downloads = scan_all_links(parsed);
+            if downloads.is_empty() {
+                return Err(e.into());
+            }
+            Ok(downloads)
+        }
     }
+}
 
-    let old_version_selector = scraper::Selector::parse(
-        r#"div.flex.justify-center.items-center.flex-wrap.lg\:px-5.relative.z-10.md\:pt-40.pt-28"#,
-    )
-    .unwrap();
+fn parse_latest_section(parsed: &scraper::Html) -> Result<Vec<DownloadInfo>, ScrapeError> {
+    let selector = scraper::Selector::parse(DOWNLOAD_HEADER_SELECTOR).unwrap();
+    let download_header = parsed
+        .select(&selector)
+        .find(|e| {
+            e.first_child()
+                .map(|e| e.value().as_text().map(|t| t.trim()) == Some("DropBoxからダウンロード"))
+                .unwrap_or(false)
+        })
+        .ok_or_else(|| {
+            ScrapeError::new(
+                ScrapeStage::Header,
+                LATEST_SECTION,
+                DOWNLOAD_HEADER_SELECTOR,
+                parsed.select(&selector).next(),
+            )
+        })?;
+
+    let table = download_header
+        .next_sibling()
+        .and_then(scraper::ElementRef::wrap)
+        .ok_or_else(|| {
+            ScrapeError::new(
+                ScrapeStage::Table,
+                LATEST_SECTION,
+                DOWNLOAD_HEADER_SELECTOR,
+                Some(download_header),
+            )
+        })?;
+
+    parse_links(table, latest_name_pattern(), LATEST_SECTION)
+}
 
+fn parse_old_version_section(parsed: &scraper::Html) -> Result<Vec<DownloadInfo>, ScrapeError> {
+    let selector = scraper::Selector::parse(OLD_VERSION_HEADER_SELECTOR).unwrap();
     let old_version_header = parsed
-        .select(&old_version_selector)
+        .select(&selector)
         .find(|e| {
             e.children()
                 .map(|e| {
                     e.first_children()
                         .next()
-                        .map(|e| e.value().as_text().map(|t| t.trim()))
-                        .flatten()
+                        .and_then(|e| e.value().as_text().map(|t| t.trim()))
                         .unwrap_or("")
                 })
                 .collect::<Vec<_>>()
                 .join("")
                 == "旧バージョン"
         })
-        .ok_or_else(|| anyhow::anyhow!("Could not find old version header"))?;
+        .ok_or_else(|| {
+            ScrapeError::new(
+                ScrapeStage::Header,
+                OLD_VERSION_SECTION,
+                OLD_VERSION_HEADER_SELECTOR,
+                parsed.select(&selector).next(),
+            )
+        })?;
 
     let old_version_table = old_version_header
         .next_sibling()
-        .ok_or_else(|| anyhow::anyhow!("Could not find old version table"))?;
+        .and_then(scraper::ElementRef::wrap)
+        .ok_or_else(|| {
+            ScrapeError::new(
+                ScrapeStage::Table,
+                OLD_VERSION_SECTION,
+                OLD_VERSION_HEADER_SELECTOR,
+                Some(old_version_header),
+            )
+        })?;
 
-    let old_version_links = scraper::ElementRef::wrap(old_version_table)
-        .ok_or_else(|| anyhow::anyhow!("Could not wrap old version table"))?
-        .select(&scraper::Selector::parse("a").unwrap())
+    parse_links(
+        old_version_table,
+        old_version_name_pattern(),
+        OLD_VERSION_SECTION,
+    )
+}
+
+fn parse_links(
+    table: scraper::ElementRef,
+    name_pattern: &regex::Regex,
+    section: &'static str,
+) -> Result<Vec<DownloadInfo>, ScrapeError> {
+    let links = table
+        .select(&scraper::Selector::parse(LINK_SELECTOR).unwrap())
         .collect::<Vec<_>>();
 
-    let old_version_name_pattern =
-        regex!(r#"v\.(?P<version>[0-9.]+)-(?P<os>windows|mac)-(?P<edition>cpu|gpu)"#);
+    let mut downloads = Vec::new();
 
-    for link in old_version_links {
-        let text = link
-            .text()
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("Could not find text"))?;
+    for link in &links {
+        let text = link.text().next().ok_or_else(|| {
+            ScrapeError::new(ScrapeStage::LinkText, section, LINK_SELECTOR, Some(*link))
+        })?;
 
-        let Some(captures) = old_version_name_pattern.captures(text) else {
+        let Some(download) = parse_link(*link, text, name_pattern) else {
             warn!("Could not find captures for {}", text);
             continue;
         };
 
-        let edition = captures["edition"].parse()?;
+        downloads.push(
+            download
+                .map_err(|stage| ScrapeError::new(stage, section, LINK_SELECTOR, Some(*link)))?,
+        );
+    }
+
+    if downloads.is_empty() {
+        return Err(ScrapeError::new(
+            ScrapeStage::RegexCapture,
+            section,
+            LINK_SELECTOR,
+            Some(table),
+        ));
+    }
+
+    Ok(downloads)
+}
+
+/// Returns `None` if `text` is not a COEIROINK download name, and `Some(Err(_))` if it is but the
+/// link is unusable.
+fn parse_link(
+    link: scraper::ElementRef,
+    text: &str,
+    name_pattern: &regex::Regex,
+) -> Option<Result<DownloadInfo, ScrapeStage>> {
+    let captures = name_pattern.captures(text)?;
 
-        let version = captures["version"].to_string();
+    let version = match captures["version"].parse::<CoeiroinkVersion>() {
+        Ok(version) => version,
+        Err(e) => {
+            warn!("Skipping {}: {}", text, e);
+            return None;
+        }
+    };
 
-        let os = captures["os"].parse()?;
+    let (Ok(edition), Ok(os)) = (captures["edition"].parse(), captures["os"].parse()) else {
+        return Some(Err(ScrapeStage::RegexCapture));
+    };
 
-        let link = link
-            .value()
-            .attr("href")
-            .ok_or_else(|| anyhow::anyhow!("Could not find link"))?;
+    let Some(href) = link.value().attr("href") else {
+        return Some(Err(ScrapeStage::Href));
+    };
 
-        downloads.push(DownloadInfo {
-            edition,
-            os,
-            version,
-            link: link.to_string(),
-        });
+    let context = link_context_text(link);
+
+    Some(Ok(DownloadInfo {
+        edition,
+        os,
+        version,
+        link: href.to_string(),
+        mirrors: vec![],
+        release_date: find_date(&context),
+        size: find_size(&context),
+        sha256: None,
+    }))
+}
+
+/// Text around a download link, i.e. of the largest ancestor that contains no other link. This is
+/// where the page puts the date and archive size, if it shows them.
+fn link_context_text(link: scraper::ElementRef) -> String {
+    let link_selector = scraper::Selector::parse(LINK_SELECTOR).unwrap();
+    let mut context = link;
+    while let Some(parent) = context.parent().and_then(scraper::ElementRef::wrap) {
+        if parent.select(&link_selector).count() > 1 {
+            break;
+        }
+        context = parent;
     }
 
-    downloads.sort_by_key(|d| semver::Version::parse(&d.version).unwrap());
-    downloads.reverse();
+    context.text().collect::<Vec<_>>().join(" ")
+}
+
+fn find_date(text: &str) -> Option<String> {
+    let date_pattern =
+        regex!(r#"(?P<year>\d{4})\s*[/.\-年]\s*(?P<month>\d{1,2})\s*[/.\-月]\s*(?P<day>\d{1,2})"#);
+    let captures = date_pattern.captures(text)?;
+
+    Some(format!(
+        "{}-{:0>2}-{:0>2}",
+        &captures["year"], &captures["month"], &captures["day"]
+    ))
+}
 
-    let downloads = downloads
-        .into_iter()
-        .filter(|d| d.os == Os::Windows && d.version.starts_with("2."))
-        .collect();
+fn find_size(text: &str) -> Option<u64> {
+    let size_pattern = regex!(r#"(?i)(?P<size>\d+(?:\.\d+)?)\s*(?P<unit>[KMGT]i?B)\b"#);
+    let captures = size_pattern.captures(text)?;
 
-    Ok(downloads)
+    let size = captures["size"].parse::<f64>().ok()?;
+    let unit = captures["unit"].to_ascii_uppercase();
+    let base: f64 = if unit.contains('I') { 1024.0 } else { 1000.0 };
+    let exponent = match &unit[..1] {
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        _ => 4,
+    };
+
+    Some((size * base.powi(exponent)) as u64)
+}
+
+/// Parses the "更新履歴" section: every line starting with a version begins a new release, and the
+/// lines after it are its notes.
+fn parse_release_notes(parsed: &scraper::Html) -> Vec<ReleaseNotes> {
+    let any_selector = scraper::Selector::parse("*").unwrap();
+    let Some(changelog_header) = parsed
+        .select(&any_selector)
+        .find(|e| e.text().collect::<String>().trim() == "更新履歴")
+    else {
+        info!("No changelog section found");
+        return vec![];
+    };
+    let Some(changelog) = changelog_header
+        .next_siblings()
+        .find_map(scraper::ElementRef::wrap)
+    else {
+        warn!("Could not find changelog after its header");
+        return vec![];
+    };
+
+    let version_line_pattern = regex!(r#"^v\.?\s*(?P<version>\d+(?:\.\d+)+)"#);
+
+    let mut releases: Vec<ReleaseNotes> = vec![];
+    for line in changelog.text().map(str::trim).filter(|t| !t.is_empty()) {
+        if let Some(captures) = version_line_pattern.captures(line) {
+            match captures["version"].parse::<CoeiroinkVersion>() {
+                Ok(version) => {
+                    releases.push(ReleaseNotes {
+                        version,
+                        release_date: find_date(line),
+                        notes: String::new(),
+                    });
+                    continue;
+                }
+                Err(e) => warn!("Skipping changelog entry {}: {}", line, e),
+            }
+        }
+        let Some(release) = releases.last_mut() else {
+            continue;
+        };
+        if !release.notes.is_empty() {
+            release.notes.push('\n');
+        }
+        release.notes.push_str(line);
+    }
+
+    info!("Found {} changelog entries", releases.len());
+
+    releases
+}
+
+/// Last resort when the page layout changed: look at every link on the page and keep the ones
+/// named like a COEIROINK download.
+fn scan_all_links(parsed: &scraper::Html) -> Vec<DownloadInfo> {
+    let mut downloads: Vec<DownloadInfo> = Vec::new();
+
+    for link in parsed.select(&scraper::Selector::parse(LINK_SELECTOR).unwrap()) {
+        let text = link.text().collect::<String>();
+        let text = text.trim();
+
+        let download = parse_link(link, text, latest_name_pattern())
+            .or_else(|| parse_link(link, text, old_version_name_pattern()));
+        let Some(Ok(download)) = download else {
+            continue;
+        };
+
+        if downloads.iter().any(|d| d.link == download.link) {
+            continue;
+        }
+        downloads.push(download);
+    }
+
+    info!("Found {} downloads by scanning links", downloads.len());
+
+    downloads
 }
@@ -0,0 +1,248 @@
+use anyhow::{bail, Result};
+
+/// A COEIROINK version as it appears on the download page or in `COEIROINKv2.exe`.
+///
+/// Unlike semver this accepts any number of numeric components ("2.3", "2.3.4.1"), an optional
+/// `v`/`v.` prefix, and `-pre`/`+build` suffixes made of `[0-9A-Za-z.-]`. Missing trailing
+/// components compare as zero.
+#[derive(Debug, Clone)]
+pub struct CoeiroinkVersion {
+    raw: String,
+    numbers: Vec<u64>,
+    pre: Option<String>,
+    build: Option<String>,
+}
+
+impl CoeiroinkVersion {
+    pub fn major(&self) -> u64 {
+        self.numbers[0]
+    }
+
+    /// The version without its prefix or trailing zero components, e.g. `2.3-rc1` for `v.2.3.0-rc1`.
+    /// Versions that are equal have the same normalized form.
+    pub fn normalized(&self) -> String {
+        let numbers = match self.significant_numbers() {
+            [] => "0".to_string(),
+            numbers => numbers
+                .iter()
+                .map(u64::to_string)
+                .collect::<Vec<_>>()
+                .join("."),
+        };
+        let pre = self.pre.as_ref().map(|pre| format!("-{}", pre));
+        let build = self.build.as_ref().map(|build| format!("+{}", build));
+
+        format!(
+            "{}{}{}",
+            numbers,
+            pre.unwrap_or_default(),
+            build.unwrap_or_default()
+        )
+    }
+
+    fn significant_numbers(&self) -> &[u64] {
+        let len = self
+            .numbers
+            .iter()
+            .rposition(|n| *n != 0)
+            .map_or(0, |i| i + 1);
+        &self.numbers[..len]
+    }
+}
+
+impl std::str::FromStr for CoeiroinkVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let raw = s.trim();
+        let version = raw
+            .strip_prefix("v.")
+            .or_else(|| raw.strip_prefix('v'))
+            .or_else(|| raw.strip_prefix('V'))
+            .unwrap_or(raw);
+
+        let (version, build) = match version.split_once('+') {
+            Some((version, build)) => (version, Some(build.to_string())),
+            None => (version, None),
+        };
+        let (version, pre) = match version.split_once('-') {
+            Some((version, pre)) => (version, Some(pre.to_string())),
+            None => (version, None),
+        };
+
+        let numbers = version
+            .trim_end_matches('.')
+            .split('.')
+            .map(|n| n.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>();
+        let numbers = match numbers {
+            Ok(numbers) if !numbers.is_empty() => numbers,
+            _ => bail!("Invalid COEIROINK version: {:?}", s),
+        };
+        // The version ends up in file names, so the suffixes must not contain separators.
+        let is_valid_suffix = |suffix: &String| {
+            suffix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+        };
+        if !pre.iter().chain(&build).all(is_valid_suffix) {
+            bail!("Invalid COEIROINK version: {:?}", s);
+        }
+
+        Ok(Self {
+            raw: raw.to_string(),
+            numbers,
+            pre: pre.filter(|p| !p.is_empty()),
+            build: build.filter(|b| !b.is_empty()),
+        })
+    }
+}
+
+impl std::fmt::Display for CoeiroinkVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl Ord for CoeiroinkVersion {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let len = self.numbers.len().max(other.numbers.len());
+        let numbers = (0..len).map(|i| {
+            let a = self.numbers.get(i).copied().unwrap_or(0);
+            let b = other.numbers.get(i).copied().unwrap_or(0);
+            a.cmp(&b)
+        });
+        for ordering in numbers {
+            if ordering.is_ne() {
+                return ordering;
+            }
+        }
+
+        // A pre-release sorts before the release itself.
+        let pre = match (&self.pre, &other.pre) {
+            (None, None) => std::cmp::Ordering::Equal,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (Some(a), Some(b)) => a.cmp(b),
+        };
+
+        pre.then_with(|| self.build.cmp(&other.build))
+    }
+}
+
+impl PartialOrd for CoeiroinkVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for CoeiroinkVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for CoeiroinkVersion {}
+
+impl std::hash::Hash for CoeiroinkVersion {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.significant_numbers().hash(state);
+        self.pre.hash(state);
+        self.build.hash(state);
+    }
+}
+
+impl serde::Serialize for CoeiroinkVersion {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for CoeiroinkVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(s: &str) -> CoeiroinkVersion {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_versions() {
+        let cases = [
+            ("2.3.4", "2.3.4"),
+            ("v2.3.4", "2.3.4"),
+            ("V2.3", "2.3"),
+            ("v.2.3.0", "2.3"),
+            (" 2.3.4. ", "2.3.4"),
+            ("2.3.4.1", "2.3.4.1"),
+            ("1.0.0-rc.1", "1-rc.1"),
+            ("1.0.0-beta-2+build.5", "1-beta-2+build.5"),
+            ("0.0", "0"),
+            ("1.0-", "1"),
+        ];
+        for (raw, normalized) in cases {
+            assert_eq!(version(raw).normalized(), normalized, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_versions() {
+        let cases = [
+            "",
+            "v",
+            "latest",
+            "2.x",
+            "2..3",
+            "-1.0",
+            "1.0-rc/1",
+            "1.0-..\\..\\evil",
+            "1.0+build/../x",
+            "1.0-rc 1",
+            "1.0+a+b",
+        ];
+        for raw in cases {
+            assert!(raw.parse::<CoeiroinkVersion>().is_err(), "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn orders_versions() {
+        let ascending = [
+            "0.9.9",
+            "1.0.0-alpha",
+            "1.0.0-beta",
+            "1.0",
+            "1.0.0+build",
+            "1.0.1",
+            "1.2",
+            "1.10",
+            "2",
+            "2.0.0.1",
+        ];
+        for pair in ascending.windows(2) {
+            assert!(version(pair[0]) < version(pair[1]), "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn equal_versions_hash_alike() {
+        let hash = |v: &CoeiroinkVersion| {
+            use std::hash::{Hash, Hasher};
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            v.hash(&mut hasher);
+            hasher.finish()
+        };
+        let cases = [("2.3", "v.2.3.0"), ("1.0.0-rc1", "v1-rc1"), ("0", "0.0.0")];
+        for (a, b) in cases {
+            assert_eq!(version(a), version(b));
+            assert_eq!(hash(&version(a)), hash(&version(b)));
+        }
+    }
+}
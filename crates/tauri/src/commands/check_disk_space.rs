@@ -0,0 +1,62 @@
+use super::install_coeiroink::{installable_downloads, InstallParams};
+use crate::disk_space::VolumeSpace;
+use crate::install_progress::ESTIMATED_EXPANSION;
+use anyhow::Result;
+use path_dedot::ParseDot as _;
+use tracing::warn;
+
+/// What installing from an archive of `archive_size` bytes writes where, before the archive has
+/// been opened: the download, the bootstrap zip extracted to the temporary directory, and the 7z
+/// extracted next to the install dir. The existing install stays until the new one is in place.
+pub fn estimated_requirements(
+    app_handle: &tauri::AppHandle,
+    install_dir: &std::path::Path,
+    archive_size: u64,
+    download: bool,
+) -> Result<Vec<(std::path::PathBuf, u64)>> {
+    let mut requirements = vec![];
+    if download {
+        requirements.push((crate::download::downloads_dir(app_handle)?, archive_size));
+    }
+    requirements.push((std::env::temp_dir(), archive_size));
+    requirements.push((install_dir.to_owned(), archive_size * ESTIMATED_EXPANSION));
+
+    Ok(requirements)
+}
+
+/// The space `install_coeiroink` with `params` would need on each volume, and what is available.
+pub async fn check_disk_space(
+    app_handle: tauri::AppHandle,
+    params: InstallParams,
+) -> Result<Vec<VolumeSpace>> {
+    let install_dir = std::path::PathBuf::from(&params.path);
+    let install_dir = install_dir.parse_dot()?;
+
+    let cached_archive =
+        crate::archive_cache::find(&app_handle, &params.version, &params.edition).await?;
+    let (archive_size, download) = match cached_archive {
+        Some((path, _)) => (fs_err::tokio::metadata(&path).await?.len(), false),
+        None => {
+            let downloads = installable_downloads(app_handle.clone()).await?;
+            let download = downloads
+                .iter()
+                .find(|d| d.edition == params.edition && d.version == params.version);
+            let size = match download {
+                Some(download) => crate::download::archive_size(&app_handle, download).await,
+                None => None,
+            };
+            let Some(size) = size else {
+                warn!("Archive size is unknown, cannot estimate the required space");
+                return Ok(vec![]);
+            };
+            (size, true)
+        }
+    };
+
+    crate::disk_space::check(&estimated_requirements(
+        &app_handle,
+        &install_dir,
+        archive_size,
+        download,
+    )?)
+}
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use serde::Serialize;
 use tracing::{error, info};
 
@@ -8,8 +8,8 @@ pub struct CheckVersionResult {
     coeirobottle: Option<String>,
 }
 
-pub async fn fetch_latest_version() -> Result<CheckVersionResult> {
-    let coeiroink = fetch_latest_coeiroink_version()
+pub async fn fetch_latest_version(app_handle: tauri::AppHandle) -> Result<CheckVersionResult> {
+    let coeiroink = fetch_latest_coeiroink_version(app_handle)
         .await
         .inspect_err(|e| {
             error!("Failed to fetch latest coeiroink version: {:?}", e);
@@ -28,12 +28,19 @@ pub async fn fetch_latest_version() -> Result<CheckVersionResult> {
     })
 }
 
-async fn fetch_latest_coeiroink_version() -> Result<String> {
-    let downloads = crate::coeiroink_scraping::fetch_downloads().await?;
+async fn fetch_latest_coeiroink_version(app_handle: tauri::AppHandle) -> Result<String> {
+    let downloads = crate::coeiroink_scraping::query_downloads(
+        app_handle,
+        &crate::coeiroink_scraping::CatalogQuery::installable(),
+    )
+    .await?;
 
-    info!("Found coeiroink version: {}", downloads[0].version);
+    let Some(latest) = downloads.first() else {
+        bail!("No installable coeiroink version found");
+    };
+    info!("Found coeiroink version: {}", latest.version);
 
-    Ok(downloads[0].version.clone())
+    Ok(latest.version.to_string())
 }
 
 async fn fetch_latest_coeirobottle_version() -> Result<String> {
@@ -0,0 +1,56 @@
+use crate::coeiroink_scraping::{CatalogQuery, DownloadInfo, ReleaseNotes};
+use crate::coeiroink_version::CoeiroinkVersion;
+use anyhow::Result;
+use tracing::{info, warn};
+
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseChanges {
+    installed: Option<CoeiroinkVersion>,
+    target: CoeiroinkVersion,
+    releases: Vec<ReleaseNotes>,
+    downloads: Vec<DownloadInfo>,
+}
+
+pub async fn fetch_release_notes(
+    app_handle: tauri::AppHandle,
+    target: CoeiroinkVersion,
+) -> Result<ReleaseChanges> {
+    let installed =
+        crate::commands::get_coeiroink_version::get_coeiroink_version(app_handle.clone())
+            .await
+            .inspect_err(|e| {
+                warn!("Failed to get installed coeiroink version: {:?}", e);
+            })
+            .ok()
+            .flatten()
+            .map(|v| v.version);
+
+    let releases = crate::coeiroink_scraping::fetch_releases(app_handle.clone())
+        .await?
+        .into_iter()
+        .filter(|r| r.version <= target)
+        .filter(|r| installed.as_ref().is_none_or(|i| r.version > *i))
+        .collect::<Vec<_>>();
+
+    info!(
+        "Found {} releases between {:?} and {}",
+        releases.len(),
+        installed,
+        target
+    );
+
+    let downloads =
+        crate::coeiroink_scraping::query_downloads(app_handle, &CatalogQuery::installable())
+            .await?
+            .into_iter()
+            .filter(|d| d.version == target)
+            .collect();
+
+    Ok(ReleaseChanges {
+        installed,
+        target,
+        releases,
+        downloads,
+    })
+}
@@ -1,6 +1,7 @@
 use crate::coeiroink_scraping::Edition;
+use crate::coeiroink_version::CoeiroinkVersion;
 use anyhow::{bail, Result};
-use tracing::info;
+use tracing::{info, warn};
 
 static GET_VERSION_LOCK: once_cell::sync::Lazy<tokio::sync::Mutex<()>> =
     once_cell::sync::Lazy::new(|| tokio::sync::Mutex::new(()));
@@ -49,29 +50,66 @@ async fn get_version(exe_path: &std::path::Path) -> Result<String> {
 
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct VersionInfo {
-    pub version: String,
+    pub version: CoeiroinkVersion,
     pub edition: Edition,
 }
 
 pub async fn get_coeiroink_version(app_handle: tauri::AppHandle) -> Result<Option<VersionInfo>> {
-    let mut store = tauri_plugin_store::StoreBuilder::new("store.json").build(app_handle.clone());
-    let _ = store.load();
     info!("Getting coeiroink version");
 
-    let coeiroink_root = store.get("coeiroink_root");
-
-    let Some(coeiroink_root) = coeiroink_root.map(|v| v.as_str()).flatten() else {
+    let Some(coeiroink_root) = configured_root(&app_handle) else {
         info!("No coeiroink_root found in store, returning None");
         return Ok(None);
     };
 
-    let coeiroink_root = std::path::Path::new(coeiroink_root);
+    Ok(Some(installed_version(&coeiroink_root).await?))
+}
+
+/// The `coeiroink_root` key of the store.
+pub fn configured_root(app_handle: &tauri::AppHandle) -> Option<std::path::PathBuf> {
+    crate::settings::get(app_handle, "coeiroink_root")
+        .and_then(|v| v.as_str().map(std::path::PathBuf::from))
+}
+
+/// Makes `install_dir` the `coeiroink_root` if it holds the latest version, or, when `latest` is
+/// unknown (e.g. offline), if it is newer than the current root.
+pub async fn register_root(
+    app_handle: &tauri::AppHandle,
+    install_dir: &std::path::Path,
+    version: &CoeiroinkVersion,
+    latest: Option<&CoeiroinkVersion>,
+) -> Result<()> {
+    let should_become_root = match (latest, configured_root(app_handle)) {
+        (Some(latest), _) => version == latest,
+        (None, None) => true,
+        (None, Some(current_root)) if current_root == install_dir => true,
+        (None, Some(current_root)) => match installed_version(&current_root).await {
+            Ok(current) => *version > current.version,
+            Err(e) => {
+                warn!("Failed to get the version of {:?}: {:?}", current_root, e);
+                true
+            }
+        },
+    };
+    if !should_become_root {
+        return Ok(());
+    }
+
+    info!("Registering {:?} as coeiroink_root", install_dir);
+    crate::settings::set(
+        app_handle,
+        "coeiroink_root",
+        install_dir.to_string_lossy().to_string().into(),
+    )
+}
 
+/// Reads the version from `COEIROINKv2.exe` and tells the editions apart by the bundled cuDNN.
+pub async fn installed_version(coeiroink_root: &std::path::Path) -> Result<VersionInfo> {
     let coeiroink_v2_exe = coeiroink_root.join("COEIROINKv2.exe");
 
     info!("Getting version of {:?}", coeiroink_v2_exe);
 
-    let version = get_version(&coeiroink_v2_exe).await?;
+    let version = get_version(&coeiroink_v2_exe).await?.parse()?;
 
     info!("coeiroink version: {:?}", &version);
 
@@ -92,5 +130,5 @@ pub async fn get_coeiroink_version(app_handle: tauri::AppHandle) -> Result<Optio
 
     info!("coeiroink edition: {:?}", &edition);
 
-    Ok(Some(VersionInfo { version, edition }))
+    Ok(VersionInfo { version, edition })
 }
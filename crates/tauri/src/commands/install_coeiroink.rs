@@ -1,21 +1,34 @@
-use crate::coeiroink_scraping::Edition;
-use anyhow::Result;
+use super::check_disk_space::estimated_requirements;
+use super::get_coeiroink_version::{installed_version, register_root};
+use crate::catalog_source::CatalogSourceConfig;
+use crate::coeiroink_scraping::{CatalogQuery, DownloadInfo, Edition};
+use crate::coeiroink_version::CoeiroinkVersion;
+use crate::install_progress::{
+    is_cancelled, InstallProgress, Phase, ESTIMATED_EXPANSION, UNITS_PER_FILE, UNITS_PER_STEP,
+};
+use crate::install_transaction::InstallTransaction;
+use crate::release_manifest::load_manifest;
+use crate::sevenzip::{ArchiveEntry, SevenZipBackend as _, SevenZipConfig};
+use anyhow::{bail, Result};
 use futures_util::StreamExt;
-use lazy_regex::regex;
 use path_dedot::ParseDot as _;
-use tauri::Manager;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
 use tokio_util::compat::TokioAsyncWriteCompatExt;
-use tracing::info;
+use tracing::{info, warn};
 use windows::core::Interface;
 
-#[derive(Clone, serde::Serialize)]
-#[serde(tag = "type")]
-enum DownloadProgress {
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type", rename_all_fields = "camelCase")]
+pub enum DownloadProgress {
+    /// Waiting for another job to finish.
+    Queued,
     Initializing,
     Downloading {
         progress: u64,
+        /// 0 when the server did not say how large the file is; see `indeterminate`.
         total: u64,
+        bytes_per_sec: u64,
+        eta_secs: Option<u64>,
+        indeterminate: bool,
     },
     Extracting {
         progress: u64,
@@ -26,64 +39,59 @@ enum DownloadProgress {
         total: u64,
         current: String,
     },
+    /// Checking an existing install against the archive.
+    Verifying {
+        progress: u64,
+        total: u64,
+        current: String,
+    },
+    /// Removing the files of the previous install.
+    Removing {
+        progress: u64,
+        total: u64,
+    },
+    Moving {
+        progress: u64,
+        total: u64,
+    },
     Configuring,
     Done,
-}
-
-async fn download(app_handle: tauri::AppHandle, url: &str) -> Result<async_tempfile::TempFile> {
-    info!("Downloading coeiroink bootstrap: {}", url);
-
-    let mut zip_file = async_tempfile::TempFile::new().await?;
-    info!("Downloading to: {:?}", zip_file.file_path());
-
-    let download_response = reqwest::get(url).await?;
-    let download_size = download_response.content_length().unwrap_or(0);
-    let mut download_progress = 0;
-    let mut last_progress = 0;
-    let mut stream = download_response.bytes_stream();
-
-    while let Some(item) = stream.next().await {
-        let item = item?;
-        zip_file.write_all(&item).await?;
-        download_progress += item.len() as u64;
-        if download_progress - last_progress > 8 * 1024 * 1024 {
-            info!(
-                "Downloading coeiroink: {} / {}",
-                download_progress, download_size
-            );
-            last_progress = download_progress;
-            app_handle.emit(
-                "installing_coeiroink",
-                DownloadProgress::Downloading {
-                    progress: download_progress,
-                    total: download_size,
-                },
-            )?;
-        }
-    }
-    info!("Downloaded coeiroink");
-
-    zip_file.flush().await?;
-
-    Ok(zip_file)
+    /// Everything was cleaned up or rolled back after a cancellation.
+    Cancelled,
 }
 
 async fn extract_bootstrap(
-    app_handle: tauri::AppHandle,
+    progress: &InstallProgress,
     zip: async_zip::tokio::read::fs::ZipFileReader,
+    extract_to: Option<&std::path::Path>,
 ) -> Result<tempfile::TempDir> {
     let total_entries = zip.file().entries().len() as u64;
+    let total_bytes = zip
+        .file()
+        .entries()
+        .iter()
+        .map(|entry| entry.uncompressed_size())
+        .sum::<u64>();
+    progress.estimate(Phase::Bootstrap, total_bytes);
+    let mut requirements = vec![(std::env::temp_dir(), total_bytes)];
+    // The volumes stay until the 7z is extracted, so both have to fit at once.
+    if let Some(extract_to) = extract_to {
+        requirements.push((extract_to.to_owned(), total_bytes * ESTIMATED_EXPANSION));
+    }
+    crate::disk_space::ensure(&requirements)?;
 
     let extract_dir = tempfile::tempdir()?;
 
-    app_handle.emit(
-        "installing_coeiroink",
+    progress.report(
+        Phase::Bootstrap,
+        0,
         DownloadProgress::Extracting {
             progress: 0,
             total: total_entries,
         },
     )?;
 
+    let mut extracted_bytes = 0;
     for i in 0..zip.file().entries().len() {
         let entry = zip.reader_with_entry(i).await?;
         let path = entry.entry().filename().as_str().unwrap().to_owned();
@@ -92,8 +100,9 @@ async fn extract_bootstrap(
         }
         let path = extract_dir.path().join(path);
         info!("Extracting: {:?}", path);
-        app_handle.emit(
-            "installing_coeiroink",
+        progress.report(
+            Phase::Bootstrap,
+            extracted_bytes,
             DownloadProgress::Extracting {
                 progress: (i + 1) as u64,
                 total: total_entries,
@@ -104,8 +113,14 @@ async fn extract_bootstrap(
         } else {
             fs_err::tokio::create_dir_all(path.parent().unwrap()).await?;
             let file = fs_err::tokio::File::create(&path).await?;
-            futures::io::copy(entry, &mut file.compat_write()).await?;
+            progress
+                .cancellable(async {
+                    futures::io::copy(entry, &mut file.compat_write()).await?;
+                    Ok(())
+                })
+                .await?;
         }
+        extracted_bytes += zip.file().entries()[i].uncompressed_size();
     }
 
     info!("Extracted coeiroink bootstrap");
@@ -131,109 +146,180 @@ async fn find_first_7z(extract_dir: &tempfile::TempDir) -> Result<std::path::Pat
     Ok(first_7z)
 }
 
+/// Tests the volume set so a damaged archive is caught before the existing install is touched.
+async fn test_7z(
+    progress: &InstallProgress,
+    sevenzip: &SevenZipConfig,
+    first_7z: &std::path::Path,
+) -> Result<()> {
+    info!("Testing 7z");
+    sevenzip.test(first_7z, progress.cancel_token()).await
+}
+
 async fn list_files(
+    sevenzip: &SevenZipConfig,
     first_7z: &std::path::Path,
     exclude_speaker_infos: bool,
-) -> Result<Vec<String>> {
+) -> Result<Vec<ArchiveEntry>> {
     info!("Listing files in 7z");
-    let files = tokio::process::Command::new(assets::sevenzip_path())
-        .arg("l")
-        .arg(&first_7z)
-        .output()
-        .await?;
-
-    if !files.status.success() {
-        return Err(anyhow::anyhow!("Failed to list files in 7z"));
-    }
-
-    let stdout = String::from_utf8_lossy(&files.stdout);
-
-    let lines = stdout.lines();
-
-    let files = lines
-        .skip_while(|line| !line.starts_with("----------")) // header
-        .skip(1) // header separator
-        .take_while(|line| !line.starts_with("----------")) // footer
-        .map(|line| {
-            let spaces = regex!(r"\s+");
-            let parts = spaces.splitn(line, 6).collect::<Vec<_>>();
-            parts[parts.len() - 1].to_owned()
-        })
+    let files = sevenzip
+        .list(first_7z)
+        .await?
+        .into_iter()
         .filter(|file| {
-            if file.contains("__pycache__") {
+            if file.is_dir || file.is_under("__pycache__") {
                 return false;
             }
-            if exclude_speaker_infos && file.contains("speaker_info") {
+            if exclude_speaker_infos && file.is_under("speaker_info") {
                 return false;
             }
 
             true
         })
         .collect::<Vec<_>>();
-    info!("Found {} files in 7z", files.len());
+    info!(
+        "Found {} files in 7z ({} bytes)",
+        files.len(),
+        files.iter().map(|file| file.size).sum::<u64>()
+    );
 
     Ok(files)
 }
 
 async fn extract_7z(
-    app_handle: tauri::AppHandle,
+    progress: &InstallProgress,
+    sevenzip: &SevenZipConfig,
     first_7z: &std::path::Path,
     temporary_extract_dir: &std::path::Path,
-    files: &[String],
+    files: &[ArchiveEntry],
     exclude_speaker_infos: bool,
 ) -> Result<()> {
     info!("Extracting 7z");
-    app_handle.emit(
-        "installing_coeiroink",
+    progress.estimate(Phase::Extract, files.iter().map(|file| file.size).sum());
+    progress.report(
+        Phase::Extract,
+        0,
         DownloadProgress::Installing {
             progress: 0,
             total: files.len() as u64,
-            current: files[0].clone(),
+            current: files
+                .first()
+                .map(|file| file.path.clone())
+                .unwrap_or_default(),
         },
     )?;
 
-    let mut extract_process = tokio::process::Command::new(assets::sevenzip_path())
-        .arg("x")
-        .arg(format!("-o{}", temporary_extract_dir.to_string_lossy()))
-        .arg("-y")
-        .arg("-bb3")
-        .arg("-xr!__pycache__")
-        .args(if exclude_speaker_infos {
-            vec!["-xr!speaker_info"]
-        } else {
-            vec![]
-        })
-        .arg(&first_7z)
-        .stdout(std::process::Stdio::piped())
-        .spawn()?;
+    let total = files.len() as u64;
+    let sizes = files
+        .iter()
+        .map(|file| (file.path.clone(), file.size))
+        .collect::<std::collections::HashMap<_, _>>();
+    let mut extracted_files = 0;
+    let mut extracted_bytes = 0;
+    let reporter = progress.clone();
+    let on_entry = move |path: &str| -> Result<()> {
+        extracted_files += 1;
+        extracted_bytes += sizes.get(path).copied().unwrap_or(0);
+        reporter.report(
+            Phase::Extract,
+            extracted_bytes,
+            DownloadProgress::Installing {
+                progress: extracted_files,
+                total,
+                current: path.to_owned(),
+            },
+        )?;
 
-    let mut extract_stdout = tokio::io::BufReader::new(extract_process.stdout.as_mut().unwrap());
+        Ok(())
+    };
 
-    let mut line = String::new();
-    let mut extracted_files = 0;
-    while extract_stdout.read_line(&mut line).await? > 0 {
-        if line.starts_with("- ") {
-            extracted_files += 1;
-            app_handle.emit(
-                "installing_coeiroink",
-                DownloadProgress::Installing {
-                    progress: extracted_files,
-                    total: files.len() as u64,
-                    current: line[2..].trim().to_owned(),
-                },
-            )?;
+    let exclude = if exclude_speaker_infos {
+        vec!["__pycache__", "speaker_info"]
+    } else {
+        vec!["__pycache__"]
+    };
+    sevenzip
+        .extract(
+            first_7z,
+            temporary_extract_dir,
+            &exclude,
+            progress.cancel_token(),
+            on_entry,
+        )
+        .await?;
+
+    let files = files.to_vec();
+    let temporary_extract_dir = temporary_extract_dir.to_owned();
+    let progress = progress.clone();
+    tokio::task::spawn_blocking(move || {
+        for file in files {
+            progress.check_cancelled()?;
+            // Extraction restores the archive's modification times, so only the CRC tells.
+            if !file.matches(&file.local_path(&temporary_extract_dir), true)? {
+                bail!("Extracted file does not match the archive: {}", file.path);
+            }
+        }
+
+        Ok(())
+    })
+    .await?
+}
+
+/// Top-level entries of an existing install that get replaced; `speaker_info` is kept.
+pub fn replaced_entries(install_dir: &std::path::Path) -> Result<Vec<std::path::PathBuf>> {
+    if !install_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut entries = vec![];
+    for entry in fs_err::read_dir(install_dir)? {
+        let entry = entry?;
+        if entry.file_name() != "speaker_info" {
+            entries.push(entry.path());
         }
-        line.clear();
     }
 
-    if !extract_process.wait().await?.success() {
-        return Err(anyhow::anyhow!("Failed to extract 7z"));
+    Ok(entries)
+}
+
+/// Number of files below `path`, not counting the directories themselves.
+pub fn count_files(path: &std::path::Path) -> Result<u64> {
+    if !fs_err::symlink_metadata(path)?.is_dir() {
+        return Ok(1);
+    }
+
+    let mut count = 0;
+    for entry in fs_err::read_dir(path)? {
+        count += count_files(&entry?.path())?;
+    }
+
+    Ok(count)
+}
+
+/// Like `remove_dir_all`, but one file at a time so that `on_file` can report progress. `on_file`
+/// gets the size of each removed file.
+pub fn remove_files(
+    path: &std::path::Path,
+    on_file: &mut impl FnMut(u64) -> Result<()>,
+) -> Result<()> {
+    let metadata = fs_err::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        fs_err::remove_file(path)?;
+        return on_file(metadata.len());
     }
 
+    for entry in fs_err::read_dir(path)? {
+        remove_files(&entry?.path(), on_file)?;
+    }
+    fs_err::remove_dir(path)?;
+
     Ok(())
 }
 
+/// Moves the extracted tree into `install_dir`, backing up what it replaces through `transaction`.
 async fn move_coeiroink(
+    progress: &InstallProgress,
+    transaction: &mut InstallTransaction,
     temporary_extract_dir: &std::path::Path,
     install_dir: &std::path::Path,
     exclude_speaker_infos: bool,
@@ -252,22 +338,7 @@ async fn move_coeiroink(
         &actual_extracted_dir, install_dir
     );
 
-    if install_dir.exists() {
-        info!("Removing existing install dir, except speaker_info");
-        let mut files = fs_err::tokio::read_dir(&install_dir).await?;
-        while let Some(entry) = files.next_entry().await? {
-            if entry.file_name() == "speaker_info" {
-                continue;
-            }
-            info!("Removing {:?}", entry.path());
-            if entry.file_type().await?.is_dir() {
-                fs_err::tokio::remove_dir_all(entry.path()).await?;
-            } else {
-                fs_err::tokio::remove_file(entry.path()).await?;
-            }
-        }
-    }
-    fs_err::tokio::create_dir_all(&install_dir).await?;
+    let replaced = replaced_entries(install_dir)?;
 
     let mut file_paths: Vec<std::path::PathBuf> = vec![];
     let mut files = fs_err::tokio::read_dir(&actual_extracted_dir).await?;
@@ -280,28 +351,91 @@ async fn move_coeiroink(
         }
         file_paths.push(entry.path());
     }
-    info!("Moving {} files", file_paths.len());
 
-    let install_dir = install_dir.to_owned();
+    let total = (replaced.len() + file_paths.len()) as u64;
+    progress.estimate(Phase::Move, total * UNITS_PER_STEP);
+    let mut moved = 0;
+    let mut report_moved = || {
+        moved += 1;
+        progress.report(
+            Phase::Move,
+            moved * UNITS_PER_STEP,
+            DownloadProgress::Moving {
+                progress: moved,
+                total,
+            },
+        )
+    };
+
+    if !replaced.is_empty() {
+        info!(
+            "Backing up existing install dir, except speaker_info, to {:?}",
+            transaction.backup_dir()
+        );
+    }
+    for path in replaced {
+        transaction.back_up(&path)?;
+        report_moved()?;
+    }
+    fs_err::tokio::create_dir_all(&install_dir).await?;
+
+    info!("Moving {} files", file_paths.len());
     for file_path in file_paths {
         let file_name = file_path.file_name().unwrap();
         let install_path = install_dir.join(file_name);
         if install_path.exists() {
-            if install_path.is_dir() {
-                fs_err::tokio::remove_dir_all(&install_path).await?;
-            } else {
-                fs_err::tokio::remove_file(&install_path).await?;
-            }
+            transaction.back_up(&install_path)?;
         }
 
         info!("Moving {:?} -> {:?}", &file_path, &install_path);
 
-        fs_err::tokio::rename(&file_path, &install_path).await?;
+        transaction.install(&file_path, &install_path)?;
+        report_moved()?;
     }
 
     Ok(())
 }
 
+/// Deletes backups beyond the newest `keep`, reporting each file as it goes. This runs after the
+/// new install is committed, so it ignores cancellation instead of leaving a half deleted backup.
+async fn remove_stale_backups(
+    progress: &InstallProgress,
+    install_dir: &std::path::Path,
+    keep: usize,
+) -> Result<()> {
+    let progress = progress.clone();
+    let install_dir = install_dir.to_owned();
+    tokio::task::spawn_blocking(move || {
+        let stale = crate::install_transaction::stale_backups(&install_dir, keep)?;
+        let total = stale
+            .iter()
+            .map(|path| count_files(path))
+            .sum::<Result<u64>>()?;
+        progress.estimate(Phase::Cleanup, total * UNITS_PER_FILE);
+
+        let mut removed = 0;
+        let mut on_file = |_| {
+            removed += 1;
+            progress.advance(
+                Phase::Cleanup,
+                removed * UNITS_PER_FILE,
+                DownloadProgress::Removing {
+                    progress: removed,
+                    total,
+                },
+            )
+        };
+        for path in stale {
+            info!("Removing backup {:?}", path);
+            remove_files(&path, &mut on_file)?;
+        }
+        let _ = fs_err::remove_dir(crate::install_transaction::backups_root(&install_dir));
+
+        Ok(())
+    })
+    .await?
+}
+
 struct Com();
 
 impl Com {
@@ -325,6 +459,21 @@ impl Drop for Com {
     }
 }
 
+pub fn desktop_shortcut_path() -> std::path::PathBuf {
+    std::path::PathBuf::from(std::env::var("USERPROFILE").unwrap())
+        .join("Desktop")
+        .join("Coeiroink v2.lnk")
+}
+
+pub fn start_menu_shortcut_path() -> std::path::PathBuf {
+    std::path::PathBuf::from(std::env::var("APPDATA").unwrap())
+        .join("Microsoft")
+        .join("Windows")
+        .join("Start Menu")
+        .join("Programs")
+        .join("Coeiroink v2.lnk")
+}
+
 async fn create_shortcut(install_dir: &std::path::Path, path: &std::path::Path) -> Result<()> {
     info!("Setting up shortcuts");
     let _com = Com::new()?;
@@ -362,73 +511,156 @@ async fn create_shortcut(install_dir: &std::path::Path, path: &std::path::Path)
     Ok(())
 }
 
+/// The file a `.lnk` points to.
+pub async fn shortcut_target(path: &std::path::Path) -> Result<std::path::PathBuf> {
+    let _com = Com::new()?;
+
+    let mut target = [0u16; 261];
+    unsafe {
+        let shell_link: windows::Win32::UI::Shell::IShellLinkW =
+            windows::Win32::System::Com::CoCreateInstance(
+                &windows::Win32::UI::Shell::ShellLink,
+                None,
+                windows::Win32::System::Com::CLSCTX_LOCAL_SERVER,
+            )?;
+
+        shell_link
+            .cast::<windows::Win32::System::Com::IPersistFile>()?
+            .Load(
+                &windows::core::HSTRING::from(path.as_os_str()),
+                windows::Win32::System::Com::STGM_READ,
+            )?;
+        shell_link.GetPath(&mut target, std::ptr::null_mut(), 0)?;
+    }
+    let length = target.iter().position(|c| *c == 0).unwrap_or(target.len());
+
+    Ok(std::path::PathBuf::from(String::from_utf16_lossy(
+        &target[..length],
+    )))
+}
+
+/// Resolves against the release manifest directly when one is configured, so that installing never
+/// depends on the scraped (or cached) catalog.
+pub async fn installable_downloads(app_handle: tauri::AppHandle) -> Result<Vec<DownloadInfo>> {
+    let CatalogSourceConfig::JsonManifest { location } =
+        crate::catalog_source::configured_source(&app_handle)?
+    else {
+        return crate::coeiroink_scraping::query_downloads(
+            app_handle,
+            &CatalogQuery::installable(),
+        )
+        .await;
+    };
+
+    let client = crate::http_client::client(&app_handle)?;
+    let manifest = crate::retry::retry(
+        &crate::retry::RetryPolicy::configured(&app_handle),
+        "Loading the release manifest",
+        || load_manifest(&client, &location),
+    )
+    .await?;
+    let mut downloads = CatalogQuery::installable().filter(manifest.to_catalog().downloads);
+    downloads.sort_by(|a, b| b.version.cmp(&a.version));
+
+    Ok(downloads)
+}
+
 #[derive(Debug, Clone, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct InstallParams {
     pub edition: Edition,
-    pub version: String,
+    pub version: CoeiroinkVersion,
     pub path: String,
     pub desktop_shortcut: bool,
     pub start_menu_shortcut: bool,
 }
 
-pub async fn install_coeiroink(app_handle: tauri::AppHandle, params: InstallParams) -> Result<()> {
-    info!("Installing coeiroink");
-    let edition = params.edition;
-    let version = params.version;
-    let path = params.path;
-
-    let install_dir = std::path::PathBuf::from(path);
-    let install_dir = install_dir.parse_dot()?;
-
-    let temporary_extract_dir = if cfg!(windows) {
+pub fn temporary_extract_dir(install_dir: &std::path::Path) -> Result<tempfile::TempDir> {
+    if cfg!(windows) {
         let install_dir_drive = install_dir
             .to_string_lossy()
             .chars()
             .next()
             .ok_or_else(|| anyhow::anyhow!("Could not get drive letter"))?;
 
-        tempfile::Builder::new()
+        Ok(tempfile::Builder::new()
             .prefix("coeirobottle_temporary_directory_")
-            .tempdir_in(format!("{}:\\", install_dir_drive))?
+            .tempdir_in(format!("{}:\\", install_dir_drive))?)
     } else {
         panic!("Unsupported platform");
-    };
+    }
+}
+
+/// The first 7z volume of a bootstrap zip, or of an already extracted volume set (`.001`). The
+/// volumes of a zip are extracted to a temporary dir, which must be kept until they are no longer
+/// needed. `extract_to` is where the 7z will be extracted, if it is, for the disk space check.
+pub async fn open_archive(
+    progress: &InstallProgress,
+    archive_path: &std::path::Path,
+    extract_to: Option<&std::path::Path>,
+) -> Result<(std::path::PathBuf, Option<tempfile::TempDir>)> {
+    if archive_path.to_string_lossy().ends_with(".001") {
+        progress.estimate(Phase::Bootstrap, 0);
+        return Ok((archive_path.to_owned(), None));
+    }
+
+    let zip = async_zip::tokio::read::fs::ZipFileReader::new(archive_path).await?;
+    let bootstrap_dir = extract_bootstrap(progress, zip, extract_to).await?;
+
+    Ok((find_first_7z(&bootstrap_dir).await?, Some(bootstrap_dir)))
+}
+
+/// Installs from a bootstrap zip, or from the first volume (`.001`) of an already extracted 7z
+/// volume set, into `install_dir`.
+pub async fn install_archive(
+    app_handle: tauri::AppHandle,
+    progress: &InstallProgress,
+    archive_path: &std::path::Path,
+    install_dir: &std::path::Path,
+    desktop_shortcut: bool,
+    start_menu_shortcut: bool,
+) -> Result<()> {
+    let temporary_extract_dir = temporary_extract_dir(install_dir)?;
 
     info!("Install dir: {:?}", install_dir);
     info!("Temporary extract dir: {:?}", temporary_extract_dir.path());
     let exclude_speaker_infos = install_dir.join("speaker_info").exists();
     info!("Speaker infos exist?: {}", exclude_speaker_infos);
 
-    info!("Fetching downloads");
-    app_handle.emit("installing_coeiroink", DownloadProgress::Initializing)?;
-    let downloads = crate::coeiroink_scraping::fetch_downloads().await?;
-
-    let download_item = downloads
-        .iter()
-        .find(|d| d.edition == edition && d.version == version);
-
-    let download_item = match download_item {
-        Some(download) => download,
-        None => {
-            return Err(anyhow::anyhow!(
-                "Could not find download for edition {}",
-                edition
-            ));
-        }
+    let replaced_files = {
+        let install_dir = install_dir.to_owned();
+        tokio::task::spawn_blocking(move || {
+            replaced_entries(&install_dir)?
+                .iter()
+                .map(|path| count_files(path))
+                .sum::<Result<u64>>()
+        })
+        .await??
     };
+    let keep_backups = crate::install_transaction::configured_backup_count(&app_handle);
+    if keep_backups == 0 {
+        progress.estimate(Phase::Cleanup, replaced_files * UNITS_PER_FILE);
+    }
+    let shortcuts = desktop_shortcut as u64 + start_menu_shortcut as u64;
+    progress.estimate(Phase::Shortcuts, shortcuts * UNITS_PER_STEP);
 
-    let zip_file = download(app_handle.clone(), &download_item.link).await?;
-    let zip = async_zip::tokio::read::fs::ZipFileReader::new(&zip_file.file_path()).await?;
+    // The bootstrap dir has to outlive the extraction, since the 7z volumes live in it.
+    let (first_7z, _bootstrap_dir) =
+        open_archive(progress, archive_path, Some(temporary_extract_dir.path())).await?;
 
-    let bootstrap_dir = extract_bootstrap(app_handle.clone(), zip).await?;
+    let sevenzip = crate::sevenzip::configured_backend(&app_handle)?;
 
-    let first_7z = find_first_7z(&bootstrap_dir).await?;
+    test_7z(progress, &sevenzip, &first_7z).await?;
 
-    let files = list_files(&first_7z, exclude_speaker_infos).await?;
+    let files = list_files(&sevenzip, &first_7z, exclude_speaker_infos).await?;
+    crate::disk_space::ensure(&[(
+        temporary_extract_dir.path().to_owned(),
+        files.iter().map(|file| file.size).sum(),
+    )])?;
 
     extract_7z(
-        app_handle.clone(),
+        progress,
+        &sevenzip,
         &first_7z,
         temporary_extract_dir.path(),
         &files,
@@ -436,47 +668,203 @@ pub async fn install_coeiroink(app_handle: tauri::AppHandle, params: InstallPara
     )
     .await?;
 
-    app_handle.emit("installing_coeiroink", DownloadProgress::Configuring)?;
-
+    let label = match installed_version(install_dir).await {
+        Ok(installed) => format!("{}-{}", installed.version.normalized(), installed.edition),
+        Err(_) => "unknown".to_string(),
+    };
+    let mut transaction = InstallTransaction::begin(install_dir, &label);
     move_coeiroink(
+        progress,
+        &mut transaction,
         temporary_extract_dir.path(),
-        &install_dir,
+        install_dir,
         exclude_speaker_infos,
     )
     .await?;
-    if params.desktop_shortcut {
-        let desktop = std::path::PathBuf::from(std::env::var("USERPROFILE").unwrap())
-            .join("Desktop")
-            .join("Coeiroink v2.lnk");
 
-        create_shortcut(&install_dir, &desktop).await?;
+    progress.report(Phase::Shortcuts, 0, DownloadProgress::Configuring)?;
+    let mut created_shortcuts = 0;
+    if desktop_shortcut {
+        create_shortcut(install_dir, &desktop_shortcut_path()).await?;
+        created_shortcuts += 1;
+        progress.report(
+            Phase::Shortcuts,
+            created_shortcuts * UNITS_PER_STEP,
+            DownloadProgress::Configuring,
+        )?;
     }
-    if params.start_menu_shortcut {
-        let start_menu = std::path::PathBuf::from(std::env::var("APPDATA").unwrap())
-            .join("Microsoft")
-            .join("Windows")
-            .join("Start Menu")
-            .join("Programs")
-            .join("Coeiroink v2.lnk");
-
-        create_shortcut(&install_dir, &start_menu).await?;
+    if start_menu_shortcut {
+        create_shortcut(install_dir, &start_menu_shortcut_path()).await?;
+        created_shortcuts += 1;
+        progress.report(
+            Phase::Shortcuts,
+            created_shortcuts * UNITS_PER_STEP,
+            DownloadProgress::Configuring,
+        )?;
     }
 
-    let mut store = tauri_plugin_store::StoreBuilder::new("store.json").build(app_handle.clone());
+    if let Some(backup_dir) = transaction.commit() {
+        info!("Previous install backed up to {:?}", backup_dir);
+    }
+    if let Err(e) = remove_stale_backups(progress, install_dir, keep_backups).await {
+        warn!("Failed to remove old backups: {:?}", e);
+    }
 
-    // is latest version
-    if downloads[0].version == version {
-        store.insert(
-            "coeiroink_root".into(),
-            install_dir.to_string_lossy().to_string().into(),
-        )?;
+    Ok(())
+}
 
-        store.save()?;
-    }
+/// The archive of `version` and `edition`: the cached one if it is intact, or else a fresh
+/// download, which is added to the cache. `requirements` gives the disk space needed for an archive
+/// of some size, and whether it is downloaded. Also returns the installable downloads, which are
+/// empty if they could not be fetched but the cached archive can be used.
+pub async fn fetch_archive(
+    app_handle: &tauri::AppHandle,
+    progress: &InstallProgress,
+    version: &CoeiroinkVersion,
+    edition: &Edition,
+    requirements: impl Fn(u64, bool) -> Result<Vec<(std::path::PathBuf, u64)>>,
+) -> Result<(std::path::PathBuf, Vec<DownloadInfo>)> {
+    info!("Fetching downloads");
+    let cached_archive = crate::archive_cache::find(app_handle, version, edition).await?;
+    let downloads = match progress
+        .cancellable(installable_downloads(app_handle.clone()))
+        .await
+    {
+        Ok(downloads) => downloads,
+        Err(e) if cached_archive.is_some() && !is_cancelled(&e) => {
+            warn!(
+                "Failed to fetch downloads, using the cached archive: {:?}",
+                e
+            );
+            vec![]
+        }
+        Err(e) => return Err(e),
+    };
+
+    let download_item = downloads
+        .iter()
+        .find(|d| d.edition == *edition && d.version == *version);
+    let expected_sha256 =
+        crate::checksum::known_sha256(app_handle, download_item, version, edition).await;
+
+    let cached_archive = match cached_archive {
+        Some((cached_path, cached)) => {
+            info!("Verifying cached archive: {:?}", cached_path);
+            let actual = progress
+                .cancellable(crate::checksum::hash_file(&cached_path))
+                .await?;
+            let expected = expected_sha256.as_deref().or(cached.sha256.as_deref());
+            match crate::checksum::verify(&cached_path, expected, &actual) {
+                Ok(()) => Some(cached_path),
+                Err(e) => {
+                    warn!("Discarding corrupted cached archive: {}", e);
+                    crate::archive_cache::purge(app_handle, Some(version), Some(edition)).await?;
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    let archive_path = match cached_archive {
+        Some(cached_archive) => {
+            info!("Using cached archive: {:?}", cached_archive);
+            let archive_size = fs_err::metadata(&cached_archive)?.len();
+            crate::disk_space::ensure(&requirements(archive_size, false)?)?;
+            progress.estimate_archive(archive_size, false);
+            cached_archive
+        }
+        None => {
+            let download_item = match download_item {
+                Some(download) => download,
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "Could not find download for edition {}",
+                        edition
+                    ));
+                }
+            };
+
+            let archive_size = progress
+                .cancellable(async {
+                    Ok(crate::download::archive_size(app_handle, download_item).await)
+                })
+                .await?;
+            match archive_size {
+                Some(archive_size) => {
+                    crate::disk_space::ensure(&requirements(archive_size, true)?)?;
+                }
+                None => warn!("Archive size is unknown, cannot check the disk space yet"),
+            }
+            progress.estimate_archive(archive_size.unwrap_or(0), true);
+            // Dropping the download closes the connection; the partial file is kept for resuming.
+            let downloaded = progress
+                .cancellable(crate::download::download_any(
+                    app_handle.clone(),
+                    progress,
+                    &crate::download::candidate_urls(app_handle, download_item),
+                    expected_sha256.as_deref(),
+                ))
+                .await?;
+            crate::archive_cache::store(
+                app_handle,
+                version,
+                edition,
+                &downloaded.path,
+                &downloaded.sha256,
+            )
+            .await?
+        }
+    };
+
+    Ok((archive_path, downloads))
+}
+
+pub async fn install_coeiroink(
+    app_handle: tauri::AppHandle,
+    params: InstallParams,
+    progress: InstallProgress,
+) -> Result<()> {
+    info!("Installing coeiroink");
+    let edition = params.edition;
+    let version = params.version;
+    let path = params.path;
+
+    let install_dir = std::path::PathBuf::from(path);
+    let install_dir = install_dir.parse_dot()?;
+
+    progress.notify(DownloadProgress::Initializing)?;
+    let (zip_path, downloads) = fetch_archive(
+        &app_handle,
+        &progress,
+        &version,
+        &edition,
+        |size, download| estimated_requirements(&app_handle, &install_dir, size, download),
+    )
+    .await?;
+
+    install_archive(
+        app_handle.clone(),
+        &progress,
+        &zip_path,
+        &install_dir,
+        params.desktop_shortcut,
+        params.start_menu_shortcut,
+    )
+    .await?;
+
+    // `downloads` is empty when the cached archive was used offline.
+    register_root(
+        &app_handle,
+        &install_dir,
+        &version,
+        downloads.first().map(|d| &d.version),
+    )
+    .await?;
 
     info!("Installed coeiroink");
 
-    app_handle.emit("installing_coeiroink", DownloadProgress::Done)?;
+    progress.finish()?;
 
     Ok(())
 }
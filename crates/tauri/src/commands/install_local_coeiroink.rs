@@ -0,0 +1,147 @@
+use super::get_coeiroink_version::{installed_version, register_root, VersionInfo};
+use super::install_coeiroink::{install_archive, DownloadProgress};
+use crate::coeiroink_scraping::Edition;
+use crate::coeiroink_version::CoeiroinkVersion;
+use crate::install_progress::InstallProgress;
+use anyhow::Result;
+use lazy_regex::regex;
+use path_dedot::ParseDot as _;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalInstallParams {
+    /// A bootstrap zip as downloaded from the site, or the first volume (`.001`) of its 7z set.
+    pub archive_path: String,
+    pub path: String,
+    pub desktop_shortcut: bool,
+    pub start_menu_shortcut: bool,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Detected {
+    version: Option<CoeiroinkVersion>,
+    edition: Option<Edition>,
+}
+
+/// Picks the version and edition out of names like `COEIROINK-GPU-v.2.3.4-Windows.zip`,
+/// `v.2.3.3-windows-cpu.zip` or `coeiroink-2.3.4-gpu.zip`. Earlier names take precedence.
+fn detect_from_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Detected {
+    let version_pattern = regex!(r#"(?i)(?:^|[^0-9a-z.])v?\.?(?P<version>\d+(?:\.\d+)+)"#);
+    let edition_pattern = regex!(r#"(?i)(?:^|[^a-z])(?P<edition>cpu|gpu)(?:[^a-z]|$)"#);
+
+    let mut detected = Detected::default();
+    for name in names {
+        let name = name
+            .trim_end_matches(".001")
+            .trim_end_matches(".7z")
+            .trim_end_matches(".zip");
+        if detected.version.is_none() {
+            detected.version = version_pattern
+                .captures(name)
+                .and_then(|c| c["version"].parse().ok());
+        }
+        if detected.edition.is_none() {
+            detected.edition = edition_pattern
+                .captures(name)
+                .and_then(|c| c["edition"].parse().ok());
+        }
+    }
+
+    detected
+}
+
+async fn detect(archive_path: &std::path::Path) -> Result<Detected> {
+    let mut names = vec![];
+    if let Some(file_name) = archive_path.file_name() {
+        names.push(file_name.to_string_lossy().to_string());
+    }
+    if let Some(parent_name) = archive_path.parent().and_then(|p| p.file_name()) {
+        names.push(parent_name.to_string_lossy().to_string());
+    }
+    if !archive_path.to_string_lossy().ends_with(".001") {
+        let zip = async_zip::tokio::read::fs::ZipFileReader::new(archive_path).await?;
+        names.extend(
+            zip.file()
+                .entries()
+                .iter()
+                .filter_map(|e| e.filename().as_str().ok().map(|n| n.to_string())),
+        );
+    }
+
+    Ok(detect_from_names(names.iter().map(|n| n.as_str())))
+}
+
+pub async fn install_local_coeiroink(
+    app_handle: tauri::AppHandle,
+    params: LocalInstallParams,
+    progress: InstallProgress,
+) -> Result<VersionInfo> {
+    info!("Installing coeiroink from {}", params.archive_path);
+    let archive_path = std::path::PathBuf::from(&params.archive_path);
+    if !archive_path.is_file() {
+        anyhow::bail!("Archive not found: {}", params.archive_path);
+    }
+    let install_dir = std::path::PathBuf::from(&params.path);
+    let install_dir = install_dir.parse_dot()?;
+
+    progress.notify(DownloadProgress::Initializing)?;
+    progress.estimate_archive(fs_err::metadata(&archive_path)?.len(), false);
+    let detected = detect(&archive_path).await?;
+    info!("Detected from the archive: {:?}", detected);
+
+    install_archive(
+        app_handle.clone(),
+        &progress,
+        &archive_path,
+        &install_dir,
+        params.desktop_shortcut,
+        params.start_menu_shortcut,
+    )
+    .await?;
+
+    // The installed exe is authoritative; the names are only a fallback for when it can't be read.
+    let installed = match (installed_version(&install_dir).await, detected.clone()) {
+        (Ok(installed), _) => installed,
+        (
+            Err(e),
+            Detected {
+                version: Some(version),
+                edition: Some(edition),
+            },
+        ) => {
+            warn!(
+                "Failed to read the installed version, using the archive name: {:?}",
+                e
+            );
+            VersionInfo { version, edition }
+        }
+        (Err(e), _) => return Err(e),
+    };
+    if detected
+        .version
+        .as_ref()
+        .is_some_and(|v| *v != installed.version)
+        || detected
+            .edition
+            .as_ref()
+            .is_some_and(|e| *e != installed.edition)
+    {
+        warn!(
+            "Archive name suggested {:?}, but installed {} {}",
+            detected, installed.version, installed.edition
+        );
+    }
+
+    // Offline there is no catalog to tell the latest version, so the newer install wins.
+    register_root(&app_handle, &install_dir, &installed.version, None).await?;
+
+    info!(
+        "Installed coeiroink {} {} from a local archive",
+        installed.version, installed.edition
+    );
+
+    progress.finish()?;
+
+    Ok(installed)
+}
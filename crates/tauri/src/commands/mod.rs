@@ -1,4 +1,9 @@
+pub mod check_disk_space;
 pub mod directory_check;
 pub mod fetch_latest_version;
+pub mod fetch_release_notes;
 pub mod get_coeiroink_version;
 pub mod install_coeiroink;
+pub mod install_local_coeiroink;
+pub mod uninstall_coeiroink;
+pub mod verify_coeiroink;
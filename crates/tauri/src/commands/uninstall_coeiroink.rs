@@ -0,0 +1,248 @@
+use super::directory_check::is_safe_to_install;
+use super::get_coeiroink_version::configured_root;
+use super::install_coeiroink::{
+    count_files, desktop_shortcut_path, remove_files, replaced_entries, shortcut_target,
+    start_menu_shortcut_path, DownloadProgress,
+};
+use crate::install_progress::{InstallProgress, Phase, UNITS_PER_FILE, UNITS_PER_STEP};
+use anyhow::{bail, Result};
+use path_dedot::ParseDot as _;
+use tracing::{info, warn};
+
+/// What to do with `speaker_info`, which holds the voices users added themselves.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SpeakerInfoAction {
+    /// Leave it in the install dir, which is otherwise emptied.
+    Keep,
+    /// Copy it to `path`, which must not exist yet, before removing the install.
+    Export {
+        path: String,
+    },
+    Delete,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UninstallParams {
+    /// Defaults to `coeiroink_root`.
+    pub path: Option<String>,
+    pub speaker_info: SpeakerInfoAction,
+    /// Also removes the backups of previous installs, see `install_transaction`.
+    #[serde(default)]
+    pub remove_backups: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UninstallResult {
+    pub install_dir: std::path::PathBuf,
+    pub freed_bytes: u64,
+    pub removed_shortcuts: Vec<std::path::PathBuf>,
+    pub exported_speaker_info: Option<std::path::PathBuf>,
+}
+
+fn copy_files(
+    from: &std::path::Path,
+    to: &std::path::Path,
+    on_file: &mut impl FnMut() -> Result<()>,
+) -> Result<()> {
+    if !fs_err::symlink_metadata(from)?.is_dir() {
+        fs_err::copy(from, to)?;
+        return on_file();
+    }
+
+    fs_err::create_dir_all(to)?;
+    for entry in fs_err::read_dir(from)? {
+        let entry = entry?;
+        copy_files(&entry.path(), &to.join(entry.file_name()), on_file)?;
+    }
+
+    Ok(())
+}
+
+/// Copies `speaker_info` to `to`. Nothing is left behind if it fails or is cancelled.
+async fn export_speaker_info(
+    progress: &InstallProgress,
+    speaker_info: &std::path::Path,
+    to: &std::path::Path,
+) -> Result<()> {
+    if to.exists() {
+        bail!("{} already exists", to.display());
+    }
+    info!("Exporting {:?} to {:?}", speaker_info, to);
+
+    let progress = progress.clone();
+    let speaker_info = speaker_info.to_owned();
+    let to = to.to_owned();
+    tokio::task::spawn_blocking(move || {
+        let total = count_files(&speaker_info)?;
+        progress.estimate(Phase::Move, total * UNITS_PER_FILE);
+
+        let mut copied = 0;
+        let mut on_file = || {
+            copied += 1;
+            progress.report(
+                Phase::Move,
+                copied * UNITS_PER_FILE,
+                DownloadProgress::Moving {
+                    progress: copied,
+                    total,
+                },
+            )
+        };
+        let result = copy_files(&speaker_info, &to, &mut on_file);
+        if result.is_err() && to.exists() {
+            if let Err(e) = fs_err::remove_dir_all(&to) {
+                warn!("Failed to remove the partial export: {:?}", e);
+            }
+        }
+
+        result
+    })
+    .await?
+}
+
+/// Removes `paths`, returning the bytes freed. Once started it runs to the end, since a half
+/// removed install is of no use to anyone.
+async fn remove_install(progress: &InstallProgress, paths: Vec<std::path::PathBuf>) -> Result<u64> {
+    let progress = progress.clone();
+    tokio::task::spawn_blocking(move || {
+        let total = paths
+            .iter()
+            .map(|path| count_files(path))
+            .sum::<Result<u64>>()?;
+        progress.estimate(Phase::Cleanup, total * UNITS_PER_FILE);
+
+        let mut removed = 0;
+        let mut freed = 0;
+        let mut on_file = |size| {
+            removed += 1;
+            freed += size;
+            progress.advance(
+                Phase::Cleanup,
+                removed * UNITS_PER_FILE,
+                DownloadProgress::Removing {
+                    progress: removed,
+                    total,
+                },
+            )
+        };
+        for path in paths {
+            info!("Removing {:?}", path);
+            remove_files(&path, &mut on_file)?;
+        }
+
+        Ok(freed)
+    })
+    .await?
+}
+
+/// Removes the shortcuts that point into `install_dir`, leaving ones for other installs alone.
+async fn remove_shortcuts(
+    progress: &InstallProgress,
+    install_dir: &std::path::Path,
+) -> Result<Vec<std::path::PathBuf>> {
+    let shortcuts = [desktop_shortcut_path(), start_menu_shortcut_path()];
+    progress.estimate(Phase::Shortcuts, shortcuts.len() as u64 * UNITS_PER_STEP);
+
+    let mut removed = vec![];
+    for (index, shortcut) in shortcuts.into_iter().enumerate() {
+        if shortcut.exists() {
+            match shortcut_target(&shortcut).await {
+                Ok(target) if target.starts_with(install_dir) => {
+                    info!("Removing shortcut {:?}", shortcut);
+                    fs_err::tokio::remove_file(&shortcut).await?;
+                    removed.push(shortcut);
+                }
+                Ok(target) => info!("Keeping {:?}, which points to {:?}", shortcut, target),
+                Err(e) => warn!("Failed to read shortcut {:?}: {:?}", shortcut, e),
+            }
+        }
+        progress.advance(
+            Phase::Shortcuts,
+            (index as u64 + 1) * UNITS_PER_STEP,
+            DownloadProgress::Configuring,
+        )?;
+    }
+
+    Ok(removed)
+}
+
+pub async fn uninstall_coeiroink(
+    app_handle: tauri::AppHandle,
+    params: UninstallParams,
+    progress: InstallProgress,
+) -> Result<UninstallResult> {
+    let configured_root = configured_root(&app_handle);
+    let Some(install_dir) = params
+        .path
+        .map(std::path::PathBuf::from)
+        .or(configured_root.clone())
+    else {
+        bail!("No install to uninstall");
+    };
+    let install_dir = install_dir.parse_dot()?.to_path_buf();
+    info!("Uninstalling coeiroink from {:?}", install_dir);
+
+    // Everything in the dir is about to go, so make sure it is ours.
+    if !install_dir.is_dir() {
+        bail!("{} does not exist", install_dir.display());
+    }
+    if !is_safe_to_install(install_dir.to_string_lossy().to_string()).await? {
+        bail!(
+            "{} contains files that were not installed by CoeiroBottle",
+            install_dir.display()
+        );
+    }
+
+    progress.notify(DownloadProgress::Initializing)?;
+
+    let speaker_info = install_dir.join("speaker_info");
+    let mut exported_speaker_info = None;
+    if let SpeakerInfoAction::Export { path } = &params.speaker_info {
+        if speaker_info.exists() {
+            let to = std::path::PathBuf::from(path).parse_dot()?.to_path_buf();
+            export_speaker_info(&progress, &speaker_info, &to).await?;
+            exported_speaker_info = Some(to);
+        }
+    }
+
+    let mut paths = replaced_entries(&install_dir)?;
+    let keep_speaker_info = matches!(params.speaker_info, SpeakerInfoAction::Keep);
+    if !keep_speaker_info && speaker_info.exists() {
+        paths.push(speaker_info);
+    }
+    let backups_root = crate::install_transaction::backups_root(&install_dir);
+    if params.remove_backups && backups_root.exists() {
+        paths.push(backups_root);
+    }
+
+    // Last chance to cancel; from here on the install is gone either way.
+    progress.check_cancelled()?;
+    let freed_bytes = remove_install(&progress, paths).await?;
+    if !keep_speaker_info {
+        fs_err::tokio::remove_dir(&install_dir).await?;
+    }
+
+    let removed_shortcuts = remove_shortcuts(&progress, &install_dir).await?;
+
+    if configured_root.is_some_and(|root| root.parse_dot().is_ok_and(|root| root == install_dir)) {
+        info!("Unregistering coeiroink_root");
+        crate::settings::delete(&app_handle, "coeiroink_root")?;
+    }
+
+    info!(
+        "Uninstalled coeiroink from {:?}, freed {} bytes",
+        install_dir, freed_bytes
+    );
+
+    progress.finish()?;
+
+    Ok(UninstallResult {
+        install_dir,
+        freed_bytes,
+        removed_shortcuts,
+        exported_speaker_info,
+    })
+}
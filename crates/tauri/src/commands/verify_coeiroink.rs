@@ -0,0 +1,350 @@
+use super::get_coeiroink_version::{configured_root, installed_version};
+use super::install_coeiroink::{
+    fetch_archive, open_archive, temporary_extract_dir, DownloadProgress,
+};
+use crate::coeiroink_scraping::Edition;
+use crate::coeiroink_version::CoeiroinkVersion;
+use crate::install_progress::{InstallProgress, Phase, UNITS_PER_FILE, UNITS_PER_STEP};
+use crate::sevenzip::{ArchiveEntry, SevenZipBackend as _, SevenZipConfig};
+use anyhow::{bail, Context as _, Result};
+use path_dedot::ParseDot as _;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyParams {
+    /// Defaults to `coeiroink_root`.
+    pub path: Option<String>,
+    /// Computes the CRC of every file, instead of only of those whose modification time differs
+    /// from the archive.
+    #[serde(default)]
+    pub thorough: bool,
+    /// Re-extracts missing and modified files.
+    #[serde(default)]
+    pub repair: bool,
+}
+
+/// Paths are relative to the install dir, separated by `/`. `speaker_info` and `__pycache__` are
+/// not checked, since they change as COEIROINK is used.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyReport {
+    pub install_dir: std::path::PathBuf,
+    pub version: CoeiroinkVersion,
+    pub edition: Edition,
+    /// Number of files in the archive that were checked.
+    pub checked: u64,
+    pub missing: Vec<String>,
+    pub modified: Vec<String>,
+    /// Files that are not in the archive. They are reported, but never removed.
+    pub extra: Vec<String>,
+    /// Missing and modified files that were re-extracted, when repairing.
+    pub repaired: Vec<String>,
+}
+
+/// A file of the install, as listed in the archive.
+#[derive(Debug, Clone)]
+struct ExpectedFile {
+    /// Without the archive's top-level dir, which the installer leaves out.
+    relative: String,
+    entry: ArchiveEntry,
+}
+
+fn is_checked(components: &[&str]) -> bool {
+    components.first() != Some(&"speaker_info") && !components.contains(&"__pycache__")
+}
+
+fn expected_files(entries: Vec<ArchiveEntry>) -> Vec<ExpectedFile> {
+    entries
+        .into_iter()
+        .filter(|entry| !entry.is_dir)
+        .filter_map(|entry| {
+            let components = entry
+                .path
+                .split(['/', '\\'])
+                .filter(|c| !c.is_empty())
+                .skip(1)
+                .collect::<Vec<_>>();
+            if components.is_empty() || !is_checked(&components) {
+                return None;
+            }
+
+            Some(ExpectedFile {
+                relative: components.join("/"),
+                entry,
+            })
+        })
+        .collect()
+}
+
+/// Files below `dir` that are checked, relative to `root`.
+fn installed_files(
+    root: &std::path::Path,
+    dir: &std::path::Path,
+    files: &mut Vec<String>,
+) -> Result<()> {
+    for entry in fs_err::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let relative = path
+            .strip_prefix(root)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        if !is_checked(&relative.iter().map(|c| c.as_str()).collect::<Vec<_>>()) {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            installed_files(root, &path, files)?;
+        } else {
+            files.push(relative.join("/"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Compares the install with `expected`, returning the missing and modified files.
+async fn compare(
+    progress: &InstallProgress,
+    install_dir: &std::path::Path,
+    expected: &[ExpectedFile],
+    thorough: bool,
+) -> Result<(Vec<ExpectedFile>, Vec<ExpectedFile>)> {
+    let progress = progress.clone();
+    let install_dir = install_dir.to_owned();
+    let expected = expected.to_vec();
+    tokio::task::spawn_blocking(move || {
+        let total = expected.len() as u64;
+        progress.estimate(
+            Phase::Extract,
+            expected.iter().map(|file| file.entry.size).sum::<u64>() + total * UNITS_PER_FILE,
+        );
+
+        let mut missing = vec![];
+        let mut modified = vec![];
+        let mut checked_bytes = 0;
+        for (index, file) in expected.into_iter().enumerate() {
+            checked_bytes += file.entry.size + UNITS_PER_FILE;
+            progress.report(
+                Phase::Extract,
+                checked_bytes,
+                DownloadProgress::Verifying {
+                    progress: index as u64 + 1,
+                    total,
+                    current: file.relative.clone(),
+                },
+            )?;
+
+            let path = install_dir.join(&file.relative);
+            if !path.exists() {
+                missing.push(file);
+            } else if !file.entry.matches(&path, thorough)? {
+                modified.push(file);
+            }
+        }
+
+        Ok((missing, modified))
+    })
+    .await?
+}
+
+/// Moves `replacement` to `path`. The file at `path` is moved aside first and only removed once the
+/// replacement is in place, so that it is not lost if that fails.
+fn replace_file(replacement: &std::path::Path, path: &std::path::Path) -> Result<()> {
+    fs_err::create_dir_all(path.parent().unwrap())?;
+    if !path.exists() {
+        fs_err::rename(replacement, path)?;
+        return Ok(());
+    }
+
+    let mut aside = path.as_os_str().to_owned();
+    aside.push(".coeirobottle-old");
+    let aside = std::path::PathBuf::from(aside);
+    fs_err::rename(path, &aside)?;
+    if let Err(e) = fs_err::rename(replacement, path) {
+        if let Err(restore_error) = fs_err::rename(&aside, path) {
+            warn!("Failed to restore {:?}: {:?}", path, restore_error);
+        }
+        return Err(e.into());
+    }
+    if let Err(e) = fs_err::remove_file(&aside) {
+        warn!("Failed to remove {:?}: {:?}", aside, e);
+    }
+
+    Ok(())
+}
+
+/// Re-extracts `broken` from the archive, and puts the files in place of the broken ones.
+async fn repair(
+    progress: &InstallProgress,
+    sevenzip: &SevenZipConfig,
+    first_7z: &std::path::Path,
+    install_dir: &std::path::Path,
+    broken: Vec<ExpectedFile>,
+) -> Result<Vec<String>> {
+    let temporary_extract_dir = temporary_extract_dir(install_dir)?;
+    let total_bytes = broken.iter().map(|file| file.entry.size).sum::<u64>();
+    crate::disk_space::ensure(&[(temporary_extract_dir.path().to_owned(), total_bytes)])?;
+
+    let total = broken.len() as u64;
+    progress.estimate(Phase::Move, total_bytes + total * UNITS_PER_STEP);
+
+    info!("Re-extracting {} files", total);
+    let sizes = broken
+        .iter()
+        .map(|file| (file.entry.path.clone(), file.entry.size))
+        .collect::<std::collections::HashMap<_, _>>();
+    let mut extracted_files = 0;
+    let mut extracted_bytes = 0;
+    let reporter = progress.clone();
+    let on_entry = move |path: &str| -> Result<()> {
+        extracted_files += 1;
+        extracted_bytes += sizes.get(path).copied().unwrap_or(0);
+        reporter.report(
+            Phase::Move,
+            extracted_bytes,
+            DownloadProgress::Installing {
+                progress: extracted_files,
+                total,
+                current: path.to_owned(),
+            },
+        )
+    };
+    let paths = broken
+        .iter()
+        .map(|file| file.entry.path.clone())
+        .collect::<Vec<_>>();
+    sevenzip
+        .extract_only(
+            first_7z,
+            temporary_extract_dir.path(),
+            &paths,
+            progress.cancel_token(),
+            on_entry,
+        )
+        .await?;
+
+    let progress = progress.clone();
+    let install_dir = install_dir.to_owned();
+    tokio::task::spawn_blocking(move || {
+        let mut repaired = vec![];
+        for file in broken {
+            let extracted = file.entry.local_path(temporary_extract_dir.path());
+            // The extracted file has the archive's modification time, so only the CRC tells.
+            if !file.entry.matches(&extracted, true)? {
+                bail!(
+                    "Extracted file does not match the archive: {}",
+                    file.entry.path
+                );
+            }
+
+            let path = install_dir.join(&file.relative);
+            info!("Repairing {:?}", path);
+            replace_file(&extracted, &path)?;
+            repaired.push(file.relative);
+
+            progress.report(
+                Phase::Move,
+                total_bytes + repaired.len() as u64 * UNITS_PER_STEP,
+                DownloadProgress::Moving {
+                    progress: repaired.len() as u64,
+                    total,
+                },
+            )?;
+        }
+
+        Ok(repaired)
+    })
+    .await?
+}
+
+/// Checks an install against the file list of the archive it was installed from, fetching the
+/// archive if it is not cached, and optionally repairs it.
+pub async fn verify_coeiroink(
+    app_handle: tauri::AppHandle,
+    params: VerifyParams,
+    progress: InstallProgress,
+) -> Result<VerifyReport> {
+    let Some(install_dir) = params
+        .path
+        .map(std::path::PathBuf::from)
+        .or_else(|| configured_root(&app_handle))
+    else {
+        bail!("No install to verify");
+    };
+    let install_dir = install_dir.parse_dot()?.to_path_buf();
+    info!("Verifying coeiroink in {:?}", install_dir);
+
+    let installed = installed_version(&install_dir)
+        .await
+        .context("Could not tell which version is installed; reinstall it instead")?;
+    info!("Installed: {} {}", installed.version, installed.edition);
+
+    progress.notify(DownloadProgress::Initializing)?;
+    let (archive_path, _) = fetch_archive(
+        &app_handle,
+        &progress,
+        &installed.version,
+        &installed.edition,
+        |size, download| {
+            let mut requirements = vec![(std::env::temp_dir(), size)];
+            if download {
+                requirements.push((crate::download::downloads_dir(&app_handle)?, size));
+            }
+            Ok(requirements)
+        },
+    )
+    .await?;
+    let (first_7z, _bootstrap_dir) = open_archive(&progress, &archive_path, None).await?;
+
+    let sevenzip = crate::sevenzip::configured_backend(&app_handle)?;
+    let expected = expected_files(sevenzip.list(&first_7z).await?);
+    let (missing, modified) = compare(&progress, &install_dir, &expected, params.thorough).await?;
+
+    let mut installed_paths = vec![];
+    installed_files(&install_dir, &install_dir, &mut installed_paths)?;
+    let expected_paths = expected
+        .iter()
+        .map(|file| file.relative.as_str())
+        .collect::<std::collections::HashSet<_>>();
+    let extra = installed_paths
+        .into_iter()
+        .filter(|path| !expected_paths.contains(path.as_str()))
+        .collect::<Vec<_>>();
+    info!(
+        "Checked {} files: {} missing, {} modified, {} extra",
+        expected.len(),
+        missing.len(),
+        modified.len(),
+        extra.len()
+    );
+
+    let relative = |files: &[ExpectedFile]| {
+        files
+            .iter()
+            .map(|file| file.relative.clone())
+            .collect::<Vec<_>>()
+    };
+    let mut report = VerifyReport {
+        install_dir,
+        version: installed.version,
+        edition: installed.edition,
+        checked: expected.len() as u64,
+        missing: relative(&missing),
+        modified: relative(&modified),
+        extra,
+        repaired: vec![],
+    };
+
+    let broken = missing.into_iter().chain(modified).collect::<Vec<_>>();
+    if params.repair && !broken.is_empty() {
+        report.repaired =
+            repair(&progress, &sevenzip, &first_7z, &report.install_dir, broken).await?;
+        info!("Repaired {} files", report.repaired.len());
+    }
+
+    progress.finish()?;
+
+    Ok(report)
+}
@@ -0,0 +1,113 @@
+use anyhow::Result;
+use tracing::info;
+
+/// Kept free on top of what an install writes, for logs and anything else using the drive.
+static MARGIN: u64 = 256 * 1024 * 1024;
+
+/// Space on one volume that an install needs.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeSpace {
+    /// Root of the volume, e.g. `C:\`.
+    pub volume: std::path::PathBuf,
+    /// Includes a safety margin.
+    pub required: u64,
+    pub available: u64,
+}
+
+#[derive(Debug)]
+pub struct InsufficientSpace {
+    pub volume: std::path::PathBuf,
+    pub required: u64,
+    pub available: u64,
+}
+
+impl std::fmt::Display for InsufficientSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let gib = |bytes: u64| bytes as f64 / 1024.0 / 1024.0 / 1024.0;
+        write!(
+            f,
+            "Not enough disk space on {}: {:.1} GiB required, {:.1} GiB available",
+            self.volume.display(),
+            gib(self.required),
+            gib(self.available)
+        )
+    }
+}
+
+impl std::error::Error for InsufficientSpace {}
+
+/// The volume `path` is on. `path` does not have to exist yet.
+fn volume_of(path: &std::path::Path) -> Result<std::path::PathBuf> {
+    let Some(existing) = path.ancestors().find(|p| p.exists()) else {
+        anyhow::bail!("Could not find the drive of {}", path.display());
+    };
+    let mut volume = [0u16; 261];
+    unsafe {
+        windows::Win32::Storage::FileSystem::GetVolumePathNameW(
+            &windows::core::HSTRING::from(existing.as_os_str()),
+            &mut volume,
+        )?;
+    }
+    let length = volume.iter().position(|c| *c == 0).unwrap_or(volume.len());
+
+    Ok(std::path::PathBuf::from(String::from_utf16_lossy(
+        &volume[..length],
+    )))
+}
+
+fn available_space(volume: &std::path::Path) -> Result<u64> {
+    let mut available = 0;
+    unsafe {
+        windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExW(
+            &windows::core::HSTRING::from(volume.as_os_str()),
+            Some(&mut available),
+            None,
+            None,
+        )?;
+    }
+
+    Ok(available)
+}
+
+/// `requirements` are the bytes that will be written below each path; paths on the same volume
+/// add up.
+pub fn check(requirements: &[(std::path::PathBuf, u64)]) -> Result<Vec<VolumeSpace>> {
+    let mut volumes: Vec<VolumeSpace> = vec![];
+    for (path, bytes) in requirements {
+        if *bytes == 0 {
+            continue;
+        }
+        let volume = volume_of(path)?;
+        match volumes.iter_mut().find(|v| v.volume == volume) {
+            Some(space) => space.required += bytes,
+            None => volumes.push(VolumeSpace {
+                available: available_space(&volume)?,
+                volume,
+                required: bytes + MARGIN,
+            }),
+        }
+    }
+
+    Ok(volumes)
+}
+
+/// Fails with [`InsufficientSpace`] if any volume cannot fit its share of `requirements`.
+pub fn ensure(requirements: &[(std::path::PathBuf, u64)]) -> Result<()> {
+    for space in check(requirements)? {
+        info!(
+            "Disk space on {:?}: {} bytes required, {} bytes available",
+            space.volume, space.required, space.available
+        );
+        if space.required > space.available {
+            return Err(InsufficientSpace {
+                volume: space.volume,
+                required: space.required,
+                available: space.available,
+            }
+            .into());
+        }
+    }
+
+    Ok(())
+}
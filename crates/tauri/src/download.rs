@@ -0,0 +1,787 @@
+use crate::coeiroink_scraping::DownloadInfo;
+use crate::commands::install_coeiroink::DownloadProgress;
+use crate::http_client::HttpClient;
+use crate::install_progress::{InstallProgress, Phase};
+use anyhow::Result;
+use futures_util::StreamExt;
+use sha2::Digest;
+use tauri::Manager;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tracing::{info, warn};
+
+static DEFAULT_CONNECTIONS: u64 = 4;
+
+/// Files smaller than two of these are always downloaded over a single connection.
+static MIN_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+
+static PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Weight of the newest sample in the smoothed rate; lower is smoother but slower to react.
+static RATE_SMOOTHING: f64 = 0.2;
+
+/// Stored next to a `.part` file so an interrupted download can be resumed with a `Range` request.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PartialDownload {
+    url: String,
+    expected_length: Option<u64>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// Set when the `.part` file is preallocated and filled by parallel range requests.
+    #[serde(default)]
+    chunks: Vec<Chunk>,
+}
+
+/// The byte range `start..end` of a chunked download, of which the first `written` bytes are on disk.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Chunk {
+    start: u64,
+    end: u64,
+    written: u64,
+}
+
+impl Chunk {
+    fn is_done(&self) -> bool {
+        self.start + self.written >= self.end
+    }
+}
+
+impl PartialDownload {
+    fn validator(&self) -> Option<&str> {
+        self.etag.as_deref().or(self.last_modified.as_deref())
+    }
+}
+
+#[derive(Debug)]
+pub struct IncompleteDownload {
+    pub received: u64,
+    pub expected: u64,
+}
+
+impl std::fmt::Display for IncompleteDownload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Download ended early: {} / {} bytes",
+            self.received, self.expected
+        )
+    }
+}
+
+impl std::error::Error for IncompleteDownload {}
+
+/// The server answered a range request with something other than the requested range, e.g.
+/// because the file changed since the download started. The partial file is discarded, so
+/// retrying starts over.
+#[derive(Debug)]
+pub struct RangeNotHonored {
+    pub url: String,
+    pub status: reqwest::StatusCode,
+}
+
+impl std::fmt::Display for RangeNotHonored {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} did not honor the range request ({}), starting over",
+            self.url, self.status
+        )
+    }
+}
+
+impl std::error::Error for RangeNotHonored {}
+
+pub struct DownloadedFile {
+    pub path: std::path::PathBuf,
+    pub sha256: String,
+}
+
+fn download_key(url: &str) -> String {
+    crate::checksum::to_hex(&sha2::Sha256::digest(url.as_bytes()))
+}
+
+/// Turns raw byte counts into `DownloadProgress::Downloading` events with a smoothed rate and ETA,
+/// at most once per `PROGRESS_INTERVAL`.
+struct DownloadMeter {
+    total: Option<u64>,
+    last_sample: std::time::Instant,
+    last_progress: u64,
+    bytes_per_sec: Option<f64>,
+}
+
+impl DownloadMeter {
+    fn new(progress: u64, total: Option<u64>) -> Self {
+        Self {
+            total,
+            last_sample: std::time::Instant::now(),
+            last_progress: progress,
+            bytes_per_sec: None,
+        }
+    }
+
+    /// Returns the event to emit, if the interval has passed since the last one or `force` is set.
+    fn update(&mut self, progress: u64, force: bool) -> Option<DownloadProgress> {
+        let elapsed = self.last_sample.elapsed();
+        if elapsed >= PROGRESS_INTERVAL {
+            // Forced updates in between don't sample, a few milliseconds would skew the rate.
+            let sample = progress.saturating_sub(self.last_progress) as f64 / elapsed.as_secs_f64();
+            self.bytes_per_sec = Some(match self.bytes_per_sec {
+                Some(rate) => rate + RATE_SMOOTHING * (sample - rate),
+                None => sample,
+            });
+            self.last_sample = std::time::Instant::now();
+            self.last_progress = progress;
+        } else if !force {
+            return None;
+        }
+        let bytes_per_sec = self.bytes_per_sec.unwrap_or(0.0);
+
+        let eta_secs = self
+            .total
+            .filter(|_| bytes_per_sec >= 1.0)
+            .map(|total| (total.saturating_sub(progress) as f64 / bytes_per_sec).ceil() as u64);
+
+        Some(DownloadProgress::Downloading {
+            progress,
+            total: self.total.unwrap_or(0),
+            bytes_per_sec: bytes_per_sec as u64,
+            eta_secs,
+            indeterminate: self.total.is_none(),
+        })
+    }
+}
+
+fn configured_connections(app_handle: &tauri::AppHandle) -> u64 {
+    crate::settings::get(app_handle, "download_connections")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_CONNECTIONS)
+        .max(1)
+}
+
+pub fn downloads_dir(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf> {
+    Ok(app_handle.path().app_cache_dir()?.join("downloads"))
+}
+
+async fn read_partial(
+    meta_path: &std::path::Path,
+    part_path: &std::path::Path,
+    url: &str,
+) -> Option<(PartialDownload, u64)> {
+    if !part_path.exists() || !meta_path.exists() {
+        return None;
+    }
+    let meta = fs_err::tokio::read_to_string(meta_path)
+        .await
+        .ok()
+        .and_then(|json| serde_json::from_str::<PartialDownload>(&json).ok())?;
+    if meta.url != url || meta.validator().is_none() {
+        return None;
+    }
+    let length = fs_err::tokio::metadata(part_path).await.ok()?.len();
+
+    Some((meta, length))
+}
+
+async fn read_head(part_path: &std::path::Path) -> Result<Vec<u8>> {
+    let mut head = vec![0u8; 16];
+    let mut file = fs_err::tokio::File::open(part_path).await?;
+    let read = file.read(&mut head).await?;
+    head.truncate(read);
+
+    Ok(head)
+}
+
+fn header(response: &reqwest::Response, name: reqwest::header::HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+async fn discard_partial(part_path: &std::path::Path, meta_path: &std::path::Path) {
+    for path in [part_path, meta_path] {
+        if let Err(e) = fs_err::tokio::remove_file(path).await {
+            warn!("Failed to remove partial download: {:?}", e);
+        }
+    }
+}
+
+/// Start and total length from a `Content-Range: bytes start-end/total` header.
+fn content_range(response: &reqwest::Response) -> Option<(u64, Option<u64>)> {
+    let content_range = header(response, reqwest::header::CONTENT_RANGE)?;
+    let range = content_range.strip_prefix("bytes ")?;
+    let (range, total) = range.split_once('/')?;
+    let start = range.split_once('-')?.0.parse().ok()?;
+
+    Some((start, total.parse().ok()))
+}
+
+/// Total length from the `Content-Range: bytes */total` header of a 416 response.
+fn unsatisfied_range_total(response: &reqwest::Response) -> Option<u64> {
+    header(response, reqwest::header::CONTENT_RANGE)?
+        .strip_prefix("bytes */")?
+        .parse()
+        .ok()
+}
+
+/// Turns a `.part` file that already holds the whole archive into the downloaded file.
+async fn complete_partial(
+    part_path: &std::path::Path,
+    meta_path: &std::path::Path,
+    zip_path: &std::path::Path,
+) -> Result<DownloadedFile> {
+    info!("Partial download is already complete");
+    fs_err::tokio::rename(part_path, zip_path).await?;
+    fs_err::tokio::remove_file(meta_path).await?;
+
+    Ok(DownloadedFile {
+        sha256: crate::checksum::hash_file(zip_path).await?,
+        path: zip_path.to_owned(),
+    })
+}
+
+/// Team-configured mirrors from the `download_mirrors` key of the store. Each entry is a URL
+/// template where `{fileName}`, `{version}`, `{edition}` and `{os}` are substituted, e.g.
+/// `https://mirror.example.com/coeiroink/{fileName}`.
+fn configured_mirrors(app_handle: &tauri::AppHandle, download: &DownloadInfo) -> Vec<String> {
+    let Some(templates) =
+        crate::settings::get(app_handle, "download_mirrors").and_then(|v| v.as_array().cloned())
+    else {
+        return vec![];
+    };
+    let file_name = reqwest::Url::parse(&download.link)
+        .ok()
+        .and_then(|url| {
+            url.path_segments()
+                .and_then(|mut segments| segments.next_back().map(|s| s.to_string()))
+        })
+        .unwrap_or_default();
+
+    templates
+        .iter()
+        .filter_map(|t| t.as_str())
+        .map(|template| {
+            template
+                .replace("{fileName}", &file_name)
+                .replace("{version}", &download.version.to_string())
+                .replace("{edition}", &download.edition.to_string())
+                .replace("{os}", &download.os.to_string())
+        })
+        .collect()
+}
+
+/// The official link first, then the mirrors that came with the catalog, then the configured ones.
+pub fn candidate_urls(app_handle: &tauri::AppHandle, download: &DownloadInfo) -> Vec<String> {
+    let mut urls = vec![download.link.clone()];
+    for url in download
+        .mirrors
+        .iter()
+        .cloned()
+        .chain(configured_mirrors(app_handle, download))
+    {
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+
+    urls
+}
+
+/// Tries each candidate URL in order, retrying transient failures with backoff (which resumes the
+/// partial file), until one yields an archive matching `expected_sha256`.
+pub async fn download_any(
+    app_handle: tauri::AppHandle,
+    progress: &InstallProgress,
+    urls: &[String],
+    expected_sha256: Option<&str>,
+) -> Result<DownloadedFile> {
+    let policy = crate::retry::RetryPolicy::configured(&app_handle);
+
+    let mut last_error = None;
+    for url in urls {
+        let downloaded = crate::retry::retry(&policy, &format!("Downloading {}", url), || {
+            download(app_handle.clone(), progress, url)
+        })
+        .await;
+        let downloaded = match downloaded {
+            Ok(downloaded) => downloaded,
+            Err(e) => {
+                warn!("Giving up on {}: {:?}", url, e);
+                last_error = Some(e);
+                continue;
+            }
+        };
+
+        match crate::checksum::verify(&downloaded.path, expected_sha256, &downloaded.sha256) {
+            Ok(()) => return Ok(downloaded),
+            Err(e) => {
+                warn!("Discarding {}: {}", url, e);
+                fs_err::tokio::remove_file(&downloaded.path).await?;
+                last_error = Some(e.into());
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No download URL available")))
+}
+
+/// The size of the file behind `url`, from the response to a request for its first byte.
+async fn probe_size(client: &HttpClient, url: &str) -> Result<Option<u64>> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::RANGE, "bytes=0-0".parse()?);
+    let resolved = crate::link_resolver::resolve(client, url, headers).await?;
+    resolved.check_content_type()?;
+    let response = resolved.response;
+
+    Ok(match content_range(&response) {
+        Some((_, Some(total))) => Some(total),
+        _ if response.status() == reqwest::StatusCode::OK => response.content_length(),
+        _ => None,
+    })
+}
+
+/// The size of the archive of `download`: from the catalog if it has one, or else as reported by
+/// the first candidate URL that does. `None` if no server tells.
+pub async fn archive_size(app_handle: &tauri::AppHandle, download: &DownloadInfo) -> Option<u64> {
+    if download.size.is_some() {
+        return download.size;
+    }
+
+    let client = match crate::http_client::client(app_handle) {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Failed to build the HTTP client: {:?}", e);
+            return None;
+        }
+    };
+    for url in candidate_urls(app_handle, download) {
+        match probe_size(&client, &url).await {
+            Ok(Some(size)) => {
+                info!("Archive size reported by {}: {} bytes", url, size);
+                return Some(size);
+            }
+            Ok(None) => info!("{} did not report the archive size", url),
+            Err(e) => warn!("Failed to get the archive size from {}: {:?}", url, e),
+        }
+    }
+
+    None
+}
+
+/// Asks for the first bytes of `url` to find out whether it can be downloaded in parallel. Returns
+/// the metadata for a chunked download if the server advertises `Accept-Ranges: bytes` and the file
+/// is large enough to be worth splitting.
+async fn probe_chunked(
+    client: &HttpClient,
+    url: &str,
+    connections: u64,
+) -> Result<Option<PartialDownload>> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::RANGE, "bytes=0-15".parse()?);
+    let resolved = crate::link_resolver::resolve(client, url, headers).await?;
+    resolved.check_content_type()?;
+    let final_url = resolved.final_url().clone();
+    let response = resolved.response;
+
+    let accepts_ranges = header(&response, reqwest::header::ACCEPT_RANGES)
+        .is_some_and(|v| v.split(',').any(|unit| unit.trim() == "bytes"));
+    let total = match content_range(&response) {
+        Some((0, Some(total))) if response.status() == reqwest::StatusCode::PARTIAL_CONTENT => {
+            total
+        }
+        _ => {
+            info!("Server does not support range requests, using a single connection");
+            return Ok(None);
+        }
+    };
+    if !accepts_ranges || total < MIN_CHUNK_SIZE * 2 {
+        info!(
+            "Not splitting the download (accepts ranges: {}, {} bytes)",
+            accepts_ranges, total
+        );
+        return Ok(None);
+    }
+
+    let etag = header(&response, reqwest::header::ETAG);
+    let last_modified = header(&response, reqwest::header::LAST_MODIFIED);
+    let head = response.bytes().await?;
+    crate::link_resolver::check_zip_magic(&final_url, &head)?;
+
+    let chunk_size = total.div_ceil(connections).max(MIN_CHUNK_SIZE);
+    let chunks = (0..total)
+        .step_by(chunk_size as usize)
+        .map(|start| Chunk {
+            start,
+            end: (start + chunk_size).min(total),
+            written: 0,
+        })
+        .collect::<Vec<_>>();
+    info!(
+        "Downloading {} bytes in {} chunks of up to {} bytes",
+        total,
+        chunks.len(),
+        chunk_size
+    );
+
+    Ok(Some(PartialDownload {
+        url: url.to_string(),
+        expected_length: Some(total),
+        etag,
+        last_modified,
+        chunks,
+    }))
+}
+
+/// Fills in chunk `index` of the preallocated `part_path`, starting after whatever is already on
+/// disk. `written` in `chunks` only ever counts flushed bytes, so it is safe to persist at any time.
+async fn download_chunk(
+    client: &HttpClient,
+    url: &str,
+    validator: Option<&str>,
+    part_path: &std::path::Path,
+    chunks: &std::sync::Mutex<Vec<Chunk>>,
+    index: usize,
+) -> Result<()> {
+    let chunk = chunks.lock().unwrap()[index].clone();
+    if chunk.is_done() {
+        return Ok(());
+    }
+    let from = chunk.start + chunk.written;
+
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(
+        reqwest::header::RANGE,
+        format!("bytes={}-{}", from, chunk.end - 1).parse()?,
+    );
+    if let Some(validator) = validator {
+        headers.insert(reqwest::header::IF_RANGE, validator.parse()?);
+    }
+    let response = crate::link_resolver::resolve(client, url, headers)
+        .await?
+        .response;
+    if response.status() != reqwest::StatusCode::PARTIAL_CONTENT
+        || content_range(&response).map(|(start, _)| start) != Some(from)
+    {
+        return Err(RangeNotHonored {
+            url: url.to_string(),
+            status: response.status(),
+        }
+        .into());
+    }
+
+    let mut file = fs_err::tokio::OpenOptions::new()
+        .write(true)
+        .open(part_path)
+        .await?;
+    file.seek(std::io::SeekFrom::Start(from)).await?;
+
+    let mut position = from;
+    let mut unflushed = 0;
+    let mut stream = response.bytes_stream();
+    while let Some(item) = stream.next().await {
+        let item = item?;
+        let item = &item[..item.len().min((chunk.end - position) as usize)];
+        file.write_all(item).await?;
+        client.throttle(item.len()).await;
+        position += item.len() as u64;
+        unflushed += item.len() as u64;
+        if unflushed >= 1024 * 1024 {
+            file.flush().await?;
+            chunks.lock().unwrap()[index].written += unflushed;
+            unflushed = 0;
+        }
+        if position >= chunk.end {
+            break;
+        }
+    }
+    file.flush().await?;
+    chunks.lock().unwrap()[index].written += unflushed;
+
+    if position != chunk.end {
+        return Err(IncompleteDownload {
+            received: position - chunk.start,
+            expected: chunk.end - chunk.start,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Downloads all unfinished chunks of `meta` concurrently, reporting the combined progress and
+/// persisting the chunk state so an interrupted download resumes every chunk where it stopped.
+async fn download_chunked(
+    install_progress: &InstallProgress,
+    client: &HttpClient,
+    meta: PartialDownload,
+    part_path: &std::path::Path,
+    meta_path: &std::path::Path,
+) -> Result<()> {
+    let total = meta.expected_length.unwrap_or(0);
+    install_progress.estimate_archive(total, true);
+    if !part_path.exists() || fs_err::tokio::metadata(part_path).await?.len() != total {
+        fs_err::tokio::File::create(part_path)
+            .await?
+            .set_len(total)
+            .await?;
+    }
+
+    let chunks = std::sync::Mutex::new(meta.chunks.clone());
+    let snapshot = |chunks: &std::sync::Mutex<Vec<Chunk>>| PartialDownload {
+        chunks: chunks.lock().unwrap().clone(),
+        ..meta.clone()
+    };
+    let downloaded = |meta: &PartialDownload| meta.chunks.iter().map(|c| c.written).sum::<u64>();
+    fs_err::tokio::write(meta_path, serde_json::to_vec(&snapshot(&chunks))?).await?;
+
+    let chunk_downloads = futures_util::future::try_join_all(
+        (0..meta.chunks.len())
+            .map(|i| download_chunk(client, &meta.url, meta.validator(), part_path, &chunks, i)),
+    );
+    let mut chunk_downloads = std::pin::pin!(chunk_downloads);
+
+    let mut meter = DownloadMeter::new(downloaded(&meta), Some(total));
+    let mut last_progress = downloaded(&meta);
+    let result = loop {
+        let tick = std::pin::pin!(tokio::time::sleep(PROGRESS_INTERVAL));
+        match futures_util::future::select(chunk_downloads.as_mut(), tick).await {
+            futures_util::future::Either::Left((result, _)) => break result,
+            futures_util::future::Either::Right(_) => {
+                let current = snapshot(&chunks);
+                let progress = downloaded(&current);
+                if let Some(event) = meter.update(progress, false) {
+                    install_progress.report(Phase::Download, progress, event)?;
+                }
+                if progress != last_progress {
+                    last_progress = progress;
+                    fs_err::tokio::write(meta_path, serde_json::to_vec(&current)?).await?;
+                }
+            }
+        }
+    };
+
+    if let Err(e) = result {
+        if e.is::<RangeNotHonored>() {
+            discard_partial(part_path, meta_path).await;
+        } else {
+            fs_err::tokio::write(meta_path, serde_json::to_vec(&snapshot(&chunks))?).await?;
+        }
+        return Err(e);
+    }
+    if let Some(event) = meter.update(total, true) {
+        install_progress.report(Phase::Download, total, event)?;
+    }
+
+    let head = read_head(part_path).await?;
+    if let Err(e) = crate::link_resolver::check_zip_magic(&reqwest::Url::parse(&meta.url)?, &head) {
+        discard_partial(part_path, meta_path).await;
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/// Downloads `url` into the app cache directory and returns the completed file along with its
+/// SHA-256. If a previous attempt left a partial file behind, it is resumed when the server
+/// supports it.
+pub async fn download(
+    app_handle: tauri::AppHandle,
+    progress: &InstallProgress,
+    url: &str,
+) -> Result<DownloadedFile> {
+    info!("Downloading coeiroink bootstrap: {}", url);
+
+    let downloads_dir = downloads_dir(&app_handle)?;
+    fs_err::tokio::create_dir_all(&downloads_dir).await?;
+    let key = download_key(url);
+    let part_path = downloads_dir.join(format!("{}.part", key));
+    let meta_path = downloads_dir.join(format!("{}.json", key));
+    let zip_path = downloads_dir.join(format!("{}.zip", key));
+    info!("Downloading to: {:?}", part_path);
+
+    let partial = read_partial(&meta_path, &part_path, url).await;
+
+    let client = crate::http_client::client(&app_handle)?;
+    let connections = configured_connections(&app_handle);
+    let chunked = match &partial {
+        Some((meta, _)) if !meta.chunks.is_empty() => {
+            info!("Resuming chunked download");
+            Some(meta.clone())
+        }
+        None if connections > 1 => probe_chunked(&client, url, connections).await?,
+        _ => None,
+    };
+    if let Some(meta) = chunked {
+        download_chunked(progress, &client, meta, &part_path, &meta_path).await?;
+        info!("Downloaded coeiroink");
+        fs_err::tokio::rename(&part_path, &zip_path).await?;
+        fs_err::tokio::remove_file(&meta_path).await?;
+
+        return Ok(DownloadedFile {
+            sha256: crate::checksum::hash_file(&zip_path).await?,
+            path: zip_path,
+        });
+    }
+
+    if let Some((meta, length)) = &partial {
+        if meta.expected_length == Some(*length) {
+            return complete_partial(&part_path, &meta_path, &zip_path).await;
+        }
+    }
+
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some((meta, length)) = &partial {
+        if *length > 0 {
+            info!("Resuming download from {} bytes", length);
+            headers.insert(
+                reqwest::header::RANGE,
+                format!("bytes={}-", length).parse()?,
+            );
+            headers.insert(
+                reqwest::header::IF_RANGE,
+                meta.validator().unwrap().parse()?,
+            );
+        }
+    }
+
+    let resolved = crate::link_resolver::resolve(&client, url, headers).await?;
+    if resolved.response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // The partial download's length was not known, and it turns out there is nothing left.
+        if let Some((_, length)) = &partial {
+            if unsatisfied_range_total(&resolved.response) == Some(*length) {
+                let head = read_head(&part_path).await?;
+                if let Err(e) = crate::link_resolver::check_zip_magic(resolved.final_url(), &head) {
+                    discard_partial(&part_path, &meta_path).await;
+                    return Err(e.into());
+                }
+                return complete_partial(&part_path, &meta_path, &zip_path).await;
+            }
+        }
+
+        warn!("Server rejected the resumed range, starting over");
+        discard_partial(&part_path, &meta_path).await;
+        return Box::pin(download(app_handle, progress, url)).await;
+    }
+    resolved.check_content_type()?;
+    let final_url = resolved.final_url().clone();
+    let download_response = resolved.response;
+
+    let resume_from = match (
+        &partial,
+        download_response.status(),
+        content_range(&download_response),
+    ) {
+        (Some((_, length)), reqwest::StatusCode::PARTIAL_CONTENT, Some((start, _)))
+            if start == *length =>
+        {
+            *length
+        }
+        (Some(_), status, _) => {
+            warn!(
+                "Server did not resume the download ({}), starting over",
+                status
+            );
+            0
+        }
+        (None, _, _) => 0,
+    };
+
+    let download_size = match content_range(&download_response) {
+        Some((_, Some(total))) if resume_from > 0 => Some(total),
+        _ => download_response
+            .content_length()
+            .map(|length| length + resume_from),
+    };
+
+    if let Some(download_size) = download_size {
+        progress.estimate_archive(download_size, true);
+    }
+
+    let meta = PartialDownload {
+        url: url.to_string(),
+        expected_length: download_size,
+        etag: header(&download_response, reqwest::header::ETAG),
+        last_modified: header(&download_response, reqwest::header::LAST_MODIFIED),
+        chunks: vec![],
+    };
+    fs_err::tokio::write(&meta_path, serde_json::to_vec(&meta)?).await?;
+
+    let mut zip_file = if resume_from > 0 {
+        fs_err::tokio::OpenOptions::new()
+            .append(true)
+            .open(&part_path)
+            .await?
+    } else {
+        fs_err::tokio::File::create(&part_path).await?
+    };
+
+    let mut download_progress = resume_from;
+    let mut meter = DownloadMeter::new(resume_from, download_size);
+    let mut hasher = sha2::Sha256::new();
+    let mut head = if resume_from > 0 {
+        crate::checksum::hash_into(&mut hasher, &part_path).await?;
+        read_head(&part_path).await?
+    } else {
+        vec![]
+    };
+    let mut stream = download_response.bytes_stream();
+
+    while let Some(item) = stream.next().await {
+        let item = match item {
+            Ok(item) => item,
+            Err(e) => {
+                // Keep what we have so the retry can resume from here.
+                zip_file.flush().await?;
+                return Err(e.into());
+            }
+        };
+        if head.len() < 4 {
+            head.extend(item.iter().take(16 - head.len()));
+            if head.len() >= 4 {
+                if let Err(e) = crate::link_resolver::check_zip_magic(&final_url, &head) {
+                    drop(zip_file);
+                    discard_partial(&part_path, &meta_path).await;
+                    return Err(e.into());
+                }
+            }
+        }
+        zip_file.write_all(&item).await?;
+        client.throttle(item.len()).await;
+        hasher.update(&item);
+        download_progress += item.len() as u64;
+        if let Some(event) = meter.update(download_progress, false) {
+            progress.report(Phase::Download, download_progress, event)?;
+        }
+    }
+    if let Some(event) = meter.update(download_progress, true) {
+        progress.report(Phase::Download, download_progress, event)?;
+    }
+    zip_file.flush().await?;
+    drop(zip_file);
+
+    if let Err(e) = crate::link_resolver::check_zip_magic(&final_url, &head) {
+        discard_partial(&part_path, &meta_path).await;
+        return Err(e.into());
+    }
+
+    if let Some(download_size) = download_size.filter(|size| *size != 0) {
+        if download_progress != download_size {
+            return Err(IncompleteDownload {
+                received: download_progress,
+                expected: download_size,
+            }
+            .into());
+        }
+    }
+    info!("Downloaded coeiroink");
+
+    fs_err::tokio::rename(&part_path, &zip_path).await?;
+    fs_err::tokio::remove_file(&meta_path).await?;
+
+    Ok(DownloadedFile {
+        path: zip_path,
+        sha256: crate::checksum::to_hex(&hasher.finalize()),
+    })
+}
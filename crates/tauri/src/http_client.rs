@@ -0,0 +1,160 @@
+use anyhow::{Context as _, Result};
+use tracing::{info, warn};
+
+/// Read from the `network` key of the store.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NetworkSettings {
+    /// e.g. `http://proxy.example.com:8080`; credentials may be given in the URL.
+    pub proxy: Option<String>,
+    /// PEM files with extra root certificates, for proxies that intercept TLS.
+    pub ca_certificates: Vec<std::path::PathBuf>,
+    pub connect_timeout_secs: u64,
+    /// How long a response may stall between two reads before it is given up on.
+    pub read_timeout_secs: u64,
+    pub user_agent: Option<String>,
+    /// Shared by all downloads, including parallel chunks. `None` means unlimited.
+    pub bandwidth_limit: Option<u64>,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            ca_certificates: vec![],
+            connect_timeout_secs: 30,
+            read_timeout_secs: 60,
+            user_agent: None,
+            bandwidth_limit: None,
+        }
+    }
+}
+
+/// Sleeps as needed so that the bytes passed to `consume` do not exceed `bytes_per_sec` on
+/// average. Idle time is not saved up for later bursts.
+#[derive(Debug)]
+struct BandwidthLimiter {
+    bytes_per_sec: u64,
+    window: std::sync::Mutex<(std::time::Instant, u64)>,
+}
+
+impl BandwidthLimiter {
+    async fn consume(&self, bytes: u64) {
+        let wait = {
+            let mut window = self.window.lock().unwrap();
+            let (started, consumed) = &mut *window;
+            let due =
+                std::time::Duration::from_secs_f64(*consumed as f64 / self.bytes_per_sec as f64);
+            if started.elapsed() > due {
+                *window = (std::time::Instant::now(), 0);
+            }
+            window.1 += bytes;
+            std::time::Duration::from_secs_f64(window.1 as f64 / self.bytes_per_sec as f64)
+                .saturating_sub(window.0.elapsed())
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+fn client_builder(settings: &NetworkSettings) -> Result<reqwest::ClientBuilder> {
+    let user_agent = settings
+        .user_agent
+        .clone()
+        .unwrap_or_else(|| format!("coeirobottle/{}", env!("CARGO_PKG_VERSION")));
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(std::time::Duration::from_secs(
+            settings.connect_timeout_secs,
+        ))
+        .read_timeout(std::time::Duration::from_secs(settings.read_timeout_secs))
+        .user_agent(user_agent);
+    if let Some(proxy) = &settings.proxy {
+        builder = builder.proxy(
+            reqwest::Proxy::all(proxy).with_context(|| format!("Invalid proxy: {}", proxy))?,
+        );
+    }
+    for path in &settings.ca_certificates {
+        let pem = fs_err::read(path)?;
+        for certificate in reqwest::Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Invalid certificate: {}", path.display()))?
+        {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    Ok(builder)
+}
+
+/// Clients built from the current [`NetworkSettings`]. Cheap to clone.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    pub client: reqwest::Client,
+    /// Same settings, but redirects are returned instead of followed.
+    pub no_redirect: reqwest::Client,
+    limiter: Option<std::sync::Arc<BandwidthLimiter>>,
+}
+
+impl HttpClient {
+    pub(crate) fn build(settings: &NetworkSettings) -> Result<Self> {
+        Ok(Self {
+            client: client_builder(settings)?.build()?,
+            no_redirect: client_builder(settings)?
+                .redirect(reqwest::redirect::Policy::none())
+                .build()?,
+            limiter: settings
+                .bandwidth_limit
+                .filter(|l| *l > 0)
+                .map(|bytes_per_sec| {
+                    std::sync::Arc::new(BandwidthLimiter {
+                        bytes_per_sec,
+                        window: std::sync::Mutex::new((std::time::Instant::now(), 0)),
+                    })
+                }),
+        })
+    }
+
+    /// Call after receiving `bytes` of a download to stay within the bandwidth limit.
+    pub async fn throttle(&self, bytes: usize) {
+        if let Some(limiter) = &self.limiter {
+            limiter.consume(bytes as u64).await;
+        }
+    }
+}
+
+static HTTP_CLIENT: once_cell::sync::Lazy<std::sync::Mutex<Option<(NetworkSettings, HttpClient)>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(None));
+
+pub fn configured_settings(app_handle: &tauri::AppHandle) -> NetworkSettings {
+    crate::settings::get(app_handle, "network")
+        .and_then(|v| {
+            serde_json::from_value(v)
+                .inspect_err(|e| warn!("Invalid network settings: {:?}", e))
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+/// The shared client for the settings in the store, rebuilt whenever they change.
+pub fn client(app_handle: &tauri::AppHandle) -> Result<HttpClient> {
+    let settings = configured_settings(app_handle);
+    let mut cached = HTTP_CLIENT.lock().unwrap();
+    if let Some((cached_settings, client)) = cached.as_ref() {
+        if *cached_settings == settings {
+            return Ok(client.clone());
+        }
+    }
+
+    info!("Building HTTP client: {:?}", settings);
+    let client = HttpClient::build(&settings)?;
+    *cached = Some((settings, client.clone()));
+
+    Ok(client)
+}
+
+/// Validates `settings` by building a client from them before saving them to the store.
+pub fn save_settings(app_handle: &tauri::AppHandle, settings: NetworkSettings) -> Result<()> {
+    HttpClient::build(&settings)?;
+
+    crate::settings::set(app_handle, "network", serde_json::to_value(&settings)?)
+}
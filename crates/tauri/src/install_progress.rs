@@ -0,0 +1,210 @@
+use crate::commands::install_coeiroink::DownloadProgress;
+use crate::jobs::JobId;
+use anyhow::Result;
+use tauri::Manager;
+
+/// The steps of an install, in the order they run. Skipped steps simply have no work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Download,
+    Bootstrap,
+    Extract,
+    Move,
+    Shortcuts,
+    /// Removing the previous install, once the new one is in place.
+    Cleanup,
+}
+
+/// Work units are bytes; deleting one file costs about as much as writing this many.
+pub const UNITS_PER_FILE: u64 = 64 * 1024;
+/// A rename or shortcut, which can take a while on Windows regardless of size.
+pub const UNITS_PER_STEP: u64 = 16 * 1024 * 1024;
+/// Extracted size relative to the archive, until the archive has been listed.
+pub const ESTIMATED_EXPANSION: u64 = 2;
+
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The installation was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+pub fn is_cancelled(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.is::<Cancelled>())
+}
+
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InstallEvent {
+    job_id: JobId,
+    #[serde(flatten)]
+    detail: DownloadProgress,
+    /// 0 to 100, never decreasing over an install.
+    percent: f64,
+}
+
+#[derive(Debug)]
+struct State {
+    units: [u64; 6],
+    phase: Phase,
+    done: u64,
+    percent: f64,
+    /// The last emitted detail.
+    detail: Option<DownloadProgress>,
+}
+
+impl State {
+    fn percent(&self) -> f64 {
+        let total = self.units.iter().sum::<u64>();
+        if total == 0 {
+            return 0.0;
+        }
+        let index = self.phase as usize;
+        let before = self.units[..index].iter().sum::<u64>();
+        let current = self.done.min(self.units[index]);
+
+        (before + current) as f64 / total as f64 * 100.0
+    }
+}
+
+/// Emits `installing_coeiroink` events with an overall percentage, weighting each phase by its
+/// work. Estimates can be refined as the install learns more; the percentage only ever grows, and
+/// only reaches 100 when the install is done.
+///
+/// Also carries the cancellation of the install: every report fails with [`Cancelled`] once
+/// [`InstallProgress::cancel`] has been called.
+#[derive(Clone)]
+pub struct InstallProgress {
+    app_handle: tauri::AppHandle,
+    job_id: JobId,
+    state: std::sync::Arc<std::sync::Mutex<State>>,
+    cancel: tokio_util::sync::CancellationToken,
+}
+
+impl InstallProgress {
+    pub fn new(app_handle: tauri::AppHandle, job_id: JobId) -> Self {
+        Self {
+            app_handle,
+            job_id,
+            state: std::sync::Arc::new(std::sync::Mutex::new(State {
+                units: [0; 6],
+                phase: Phase::Download,
+                done: 0,
+                percent: 0.0,
+                detail: None,
+            })),
+            cancel: tokio_util::sync::CancellationToken::new(),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn cancel_token(&self) -> &tokio_util::sync::CancellationToken {
+        &self.cancel
+    }
+
+    pub fn check_cancelled(&self) -> Result<()> {
+        if self.cancel.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+
+        Ok(())
+    }
+
+    /// Runs `future` until it finishes or the install is cancelled, in which case it is dropped.
+    /// Only for futures that clean up after themselves on drop.
+    pub async fn cancellable<T>(
+        &self,
+        future: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let cancelled = std::pin::pin!(self.cancel.cancelled());
+        match futures_util::future::select(cancelled, std::pin::pin!(future)).await {
+            futures_util::future::Either::Left(_) => Err(Cancelled.into()),
+            futures_util::future::Either::Right((result, _)) => result,
+        }
+    }
+
+    /// The overall percentage and the last emitted detail.
+    pub fn snapshot(&self) -> (f64, Option<DownloadProgress>) {
+        let state = self.state.lock().unwrap();
+
+        (state.percent, state.detail.clone())
+    }
+
+    pub fn estimate(&self, phase: Phase, units: u64) {
+        self.state.lock().unwrap().units[phase as usize] = units;
+    }
+
+    /// Rough weights for everything derived from an archive of `size` bytes, before it is opened.
+    pub fn estimate_archive(&self, size: u64, download: bool) {
+        self.estimate(Phase::Download, if download { size } else { 0 });
+        self.estimate(Phase::Bootstrap, size);
+        self.estimate(Phase::Extract, size * ESTIMATED_EXPANSION);
+    }
+
+    /// Records that `done` units of `phase` are finished, which also completes all earlier phases,
+    /// and emits `detail` with the overall percentage.
+    pub fn report(&self, phase: Phase, done: u64, detail: DownloadProgress) -> Result<()> {
+        self.check_cancelled()?;
+
+        self.advance(phase, done, detail)
+    }
+
+    /// Like [`InstallProgress::report`], but for work that must not stop halfway once started.
+    pub fn advance(&self, phase: Phase, done: u64, detail: DownloadProgress) -> Result<()> {
+        let percent = {
+            let mut state = self.state.lock().unwrap();
+            if phase > state.phase {
+                state.phase = phase;
+            }
+            if phase == state.phase {
+                state.done = done;
+            }
+            let percent = state.percent().min(99.9);
+            state.percent = state.percent.max(percent);
+            state.percent
+        };
+
+        self.emit(detail, percent)
+    }
+
+    /// Emits a detail that does not advance the progress, e.g. `Initializing`.
+    pub fn notify(&self, detail: DownloadProgress) -> Result<()> {
+        let percent = self.state.lock().unwrap().percent;
+
+        self.emit(detail, percent)
+    }
+
+    /// Tells the UI that the install stopped because it was cancelled, after cleaning up.
+    pub fn cancelled(&self) -> Result<()> {
+        let percent = self.state.lock().unwrap().percent;
+
+        self.emit(DownloadProgress::Cancelled, percent)
+    }
+
+    pub fn finish(&self) -> Result<()> {
+        self.state.lock().unwrap().percent = 100.0;
+
+        self.emit(DownloadProgress::Done, 100.0)
+    }
+
+    fn emit(&self, detail: DownloadProgress, percent: f64) -> Result<()> {
+        self.state.lock().unwrap().detail = Some(detail.clone());
+        self.app_handle.emit(
+            "installing_coeiroink",
+            InstallEvent {
+                job_id: self.job_id,
+                detail,
+                percent,
+            },
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::Result;
+use tracing::{info, warn};
+
+/// How many previous installs to keep after a successful update, from the `backup_count` key of
+/// the store. 0 (the default) removes the backup as soon as the new install is in place.
+pub fn configured_backup_count(app_handle: &tauri::AppHandle) -> usize {
+    crate::settings::get(app_handle, "backup_count")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0) as usize
+}
+
+/// Backups live next to the install dir, so that they are on the same volume and can be renamed
+/// into place instead of copied.
+pub fn backups_root(install_dir: &std::path::Path) -> std::path::PathBuf {
+    let mut name = install_dir
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    name.push(".backups");
+
+    install_dir.with_file_name(name)
+}
+
+/// Backups of `install_dir` beyond the newest `keep`.
+pub fn stale_backups(
+    install_dir: &std::path::Path,
+    keep: usize,
+) -> Result<Vec<std::path::PathBuf>> {
+    let root = backups_root(install_dir);
+    if !root.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut backups = vec![];
+    for entry in fs_err::read_dir(&root)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            backups.push(entry.path());
+        }
+    }
+    // Names start with a timestamp.
+    backups.sort();
+    backups.reverse();
+
+    Ok(backups.into_iter().skip(keep).collect())
+}
+
+/// Replaces the contents of an install dir so that it can be undone: replaced entries are renamed
+/// into a backup dir rather than deleted. Unless [`InstallTransaction::commit`] is called, dropping
+/// it (on an error, or when the install task is aborted) puts everything back.
+pub struct InstallTransaction {
+    backup_dir: std::path::PathBuf,
+    /// (original, backup) pairs.
+    backed_up: Vec<(std::path::PathBuf, std::path::PathBuf)>,
+    installed: Vec<std::path::PathBuf>,
+    committed: bool,
+}
+
+impl InstallTransaction {
+    /// `label` identifies the replaced install in the backup's name, e.g. its version.
+    pub fn begin(install_dir: &std::path::Path, label: &str) -> Self {
+        let name = format!("{}-{}", chrono::Local::now().format("%Y%m%d-%H%M%S"), label);
+
+        Self {
+            backup_dir: backups_root(install_dir).join(name),
+            backed_up: vec![],
+            installed: vec![],
+            committed: false,
+        }
+    }
+
+    pub fn backup_dir(&self) -> &std::path::Path {
+        &self.backup_dir
+    }
+
+    pub fn back_up(&mut self, path: &std::path::Path) -> Result<()> {
+        fs_err::create_dir_all(&self.backup_dir)?;
+        let backup = self.backup_dir.join(path.file_name().unwrap());
+        fs_err::rename(path, &backup)?;
+        self.backed_up.push((path.to_owned(), backup));
+
+        Ok(())
+    }
+
+    /// Moves `from` to `to`, which must not exist.
+    pub fn install(&mut self, from: &std::path::Path, to: &std::path::Path) -> Result<()> {
+        fs_err::rename(from, to)?;
+        self.installed.push(to.to_owned());
+
+        Ok(())
+    }
+
+    /// Keeps the new install. Returns the backup dir, if anything was backed up.
+    pub fn commit(mut self) -> Option<std::path::PathBuf> {
+        self.committed = true;
+
+        (!self.backed_up.is_empty()).then(|| self.backup_dir.clone())
+    }
+
+    /// Puts the previous install back. Every step is attempted even if an earlier one failed, so
+    /// that as much as possible is restored; the backup dir is only left behind if something in it
+    /// could not be restored.
+    fn rollback(&mut self) -> Result<()> {
+        let mut errors = vec![];
+        for path in self.installed.drain(..).rev() {
+            info!("Rolling back {:?}", path);
+            let removed = fs_err::symlink_metadata(&path).and_then(|metadata| {
+                if metadata.is_dir() {
+                    fs_err::remove_dir_all(&path)
+                } else {
+                    fs_err::remove_file(&path)
+                }
+            });
+            if let Err(e) = removed {
+                errors.push(e.to_string());
+            }
+        }
+        let mut restore_failed = false;
+        for (original, backup) in self.backed_up.drain(..).rev() {
+            info!("Restoring {:?}", original);
+            if let Err(e) = fs_err::rename(&backup, &original) {
+                errors.push(e.to_string());
+                restore_failed = true;
+            }
+        }
+
+        if restore_failed {
+            anyhow::bail!(
+                "Failed to restore the previous install, it is left in {:?}: {}",
+                self.backup_dir,
+                errors.join("; ")
+            );
+        }
+        let _ = fs_err::remove_dir(&self.backup_dir);
+        let _ = fs_err::remove_dir(self.backup_dir.parent().unwrap());
+        if !errors.is_empty() {
+            anyhow::bail!(
+                "Restored the previous install, but failed to remove the new one: {}",
+                errors.join("; ")
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for InstallTransaction {
+    fn drop(&mut self) {
+        if self.committed || (self.installed.is_empty() && self.backed_up.is_empty()) {
+            return;
+        }
+
+        warn!("Install did not complete, restoring the previous install");
+        if let Err(e) = self.rollback() {
+            warn!("{:?}", e);
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use crate::commands::install_coeiroink::DownloadProgress;
+use crate::install_progress::{is_cancelled, InstallProgress};
+use anyhow::Result;
+use tauri::Manager;
+use tracing::{info, warn};
+
+/// Finished jobs kept in the history.
+static HISTORY_LIMIT: usize = 100;
+
+pub type JobId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobKind {
+    Install,
+    LocalInstall,
+    Uninstall,
+    Verify,
+    Repair,
+    PurgeArchiveCache,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobInfo {
+    pub id: JobId,
+    pub kind: JobKind,
+    /// e.g. `1.8.0 (gpu) -> C:\...\coeiroink-v2`.
+    pub description: String,
+    pub status: JobStatus,
+    pub queued_at: u64,
+    #[serde(default)]
+    pub started_at: Option<u64>,
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Overall percentage, see [`InstallProgress`].
+    #[serde(default)]
+    pub percent: f64,
+    /// The last progress event of a job that has not finished yet.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<DownloadProgress>,
+}
+
+struct ActiveJob {
+    info: JobInfo,
+    progress: InstallProgress,
+}
+
+#[derive(Default)]
+struct Jobs {
+    /// Loaded from disk on first use.
+    history: Option<Vec<JobInfo>>,
+    active: Vec<ActiveJob>,
+    next_id: JobId,
+    /// Completes when the last queued job is done. Each job takes it in [`queue`] and waits on it
+    /// before running, so jobs run one at a time in the order they were queued.
+    last_done: Option<tokio::sync::oneshot::Receiver<()>>,
+}
+
+static JOBS: once_cell::sync::Lazy<std::sync::Mutex<Jobs>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(Jobs::default()));
+
+/// Held while writing the history, so that an older snapshot never overwrites a newer one.
+static HISTORY_WRITER: once_cell::sync::Lazy<std::sync::Mutex<()>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(()));
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn history_path(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf> {
+    Ok(app_handle.path().app_data_dir()?.join("jobs.json"))
+}
+
+fn read_history(app_handle: &tauri::AppHandle) -> Vec<JobInfo> {
+    let history = history_path(app_handle).and_then(|path| {
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        Ok(serde_json::from_str::<Vec<JobInfo>>(
+            &fs_err::read_to_string(path)?,
+        )?)
+    });
+
+    history.unwrap_or_else(|e| {
+        warn!("Failed to read job history: {:?}", e);
+        vec![]
+    })
+}
+
+fn write_history(app_handle: &tauri::AppHandle) {
+    let _writer = HISTORY_WRITER.lock().unwrap();
+    let history = jobs(app_handle).history.clone().unwrap_or_default();
+
+    let written = history_path(app_handle).and_then(|path| {
+        fs_err::create_dir_all(path.parent().unwrap())?;
+        fs_err::write(path, serde_json::to_vec(&history)?)?;
+        Ok(())
+    });
+    if let Err(e) = written {
+        warn!("Failed to write job history: {:?}", e);
+    }
+}
+
+/// Locks the jobs, with the history loaded. The history is read from disk before locking, so that
+/// the lock is never held during IO.
+fn jobs(app_handle: &tauri::AppHandle) -> std::sync::MutexGuard<'static, Jobs> {
+    let loaded = JOBS.lock().unwrap().history.is_some();
+    let read = (!loaded).then(|| read_history(app_handle));
+
+    let mut jobs = JOBS.lock().unwrap();
+    if let (None, Some(history)) = (&jobs.history, read) {
+        // IDs stay unique across restarts.
+        jobs.next_id = history.iter().map(|job| job.id + 1).max().unwrap_or(0);
+        jobs.history = Some(history);
+    }
+
+    jobs
+}
+
+impl Jobs {
+    fn history_mut(&mut self) -> &mut Vec<JobInfo> {
+        self.history.get_or_insert_with(Vec::new)
+    }
+
+    fn active_mut(&mut self, id: JobId) -> Option<&mut ActiveJob> {
+        self.active.iter_mut().find(|job| job.info.id == id)
+    }
+}
+
+impl ActiveJob {
+    fn snapshot(&self) -> JobInfo {
+        let (percent, progress) = self.progress.snapshot();
+
+        JobInfo {
+            percent,
+            progress,
+            ..self.info.clone()
+        }
+    }
+}
+
+fn emit_update(app_handle: &tauri::AppHandle, job: &JobInfo) {
+    if let Err(e) = app_handle.emit("job_updated", job) {
+        warn!("Failed to emit job update: {:?}", e);
+    }
+}
+
+fn set_running(app_handle: &tauri::AppHandle, id: JobId) {
+    let update = {
+        let mut jobs = JOBS.lock().unwrap();
+        jobs.active_mut(id).map(|job| {
+            job.info.status = JobStatus::Running;
+            job.info.started_at = Some(now());
+            job.snapshot()
+        })
+    };
+
+    if let Some(update) = update {
+        emit_update(app_handle, &update);
+    }
+}
+
+fn finish<T>(app_handle: &tauri::AppHandle, id: JobId, result: &Result<T>) {
+    let info = {
+        let mut jobs = jobs(app_handle);
+        let Some(index) = jobs.active.iter().position(|job| job.info.id == id) else {
+            return;
+        };
+        let job = jobs.active.remove(index);
+        let mut info = JobInfo {
+            progress: None,
+            ..job.snapshot()
+        };
+        info.finished_at = Some(now());
+        info.status = match result {
+            Ok(_) => JobStatus::Succeeded,
+            Err(e) if is_cancelled(e) => JobStatus::Cancelled,
+            Err(e) => {
+                info.error = Some(format!("{:#}", e));
+                JobStatus::Failed
+            }
+        };
+
+        let history = jobs.history_mut();
+        history.push(info.clone());
+        let excess = history.len().saturating_sub(HISTORY_LIMIT);
+        history.drain(..excess);
+        info
+    };
+
+    info!("Job {} finished: {:?}", id, info.status);
+    write_history(app_handle);
+    emit_update(app_handle, &info);
+}
+
+/// Queues `job` behind any other running or queued job. Returns its ID right away, along with a
+/// handle for its result; the job keeps running (and is recorded in the history) even if the
+/// handle is dropped.
+pub fn queue<T, F>(
+    app_handle: tauri::AppHandle,
+    kind: JobKind,
+    description: String,
+    job: impl FnOnce(InstallProgress) -> F + Send + 'static,
+) -> (JobId, tokio::task::JoinHandle<Result<T>>)
+where
+    T: Send + 'static,
+    F: std::future::Future<Output = Result<T>> + Send + 'static,
+{
+    let (done, next_done) = tokio::sync::oneshot::channel::<()>();
+    let (id, progress, queued, mut previous) = {
+        let mut jobs = jobs(&app_handle);
+        let id = jobs.next_id;
+        jobs.next_id += 1;
+        let progress = InstallProgress::new(app_handle.clone(), id);
+        let job = ActiveJob {
+            info: JobInfo {
+                id,
+                kind,
+                description,
+                status: JobStatus::Queued,
+                queued_at: now(),
+                started_at: None,
+                finished_at: None,
+                error: None,
+                percent: 0.0,
+                progress: None,
+            },
+            progress: progress.clone(),
+        };
+        let queued = job.snapshot();
+        jobs.active.push(job);
+        let previous = jobs.last_done.replace(next_done);
+        (id, progress, queued, previous)
+    };
+    info!("Queued job {}: {}", id, queued.description);
+    emit_update(&app_handle, &queued);
+
+    let task = tokio::task::spawn(async move {
+        let run_job = async {
+            progress.notify(DownloadProgress::Queued)?;
+            if let Some(previous_done) = previous.as_mut() {
+                progress
+                    .cancellable(async {
+                        // Also completes (with an error) if the previous job's task is gone.
+                        let _ = previous_done.await;
+                        Ok(())
+                    })
+                    .await?;
+                previous = None;
+            }
+            set_running(&app_handle, id);
+
+            // A panicking job should still end up in the history.
+            tokio::task::spawn(job(progress.clone()))
+                .await
+                .unwrap_or_else(|e| Err(anyhow::anyhow!("Job {} panicked: {}", id, e)))
+        };
+        let result = run_job.await;
+
+        if result.as_ref().is_err_and(is_cancelled) {
+            if let Err(e) = progress.cancelled() {
+                warn!("{:?}", e);
+            }
+        }
+        finish(&app_handle, id, &result);
+
+        // A job cancelled while queued still lets the next one run only after the earlier ones.
+        match previous {
+            Some(previous_done) => {
+                tokio::task::spawn(async move {
+                    let _ = previous_done.await;
+                    drop(done);
+                });
+            }
+            None => drop(done),
+        }
+
+        result
+    });
+
+    (id, task)
+}
+
+/// Like [`queue`], but waits for the job to finish.
+pub async fn run<T, F>(
+    app_handle: tauri::AppHandle,
+    kind: JobKind,
+    description: String,
+    job: impl FnOnce(InstallProgress) -> F + Send + 'static,
+) -> Result<T>
+where
+    T: Send + 'static,
+    F: std::future::Future<Output = Result<T>> + Send + 'static,
+{
+    let (_, task) = queue(app_handle, kind, description, job);
+
+    task.await?
+}
+
+/// Running and queued jobs, followed by the history, newest first.
+pub fn list(app_handle: &tauri::AppHandle) -> Vec<JobInfo> {
+    let mut jobs = jobs(app_handle);
+    let mut list = jobs
+        .active
+        .iter()
+        .rev()
+        .map(ActiveJob::snapshot)
+        .collect::<Vec<_>>();
+    list.extend(jobs.history_mut().iter().rev().cloned());
+
+    list
+}
+
+pub fn get(app_handle: &tauri::AppHandle, id: JobId) -> Option<JobInfo> {
+    let mut jobs = jobs(app_handle);
+    if let Some(job) = jobs.active_mut(id) {
+        return Some(job.snapshot());
+    }
+
+    jobs.history_mut().iter().find(|job| job.id == id).cloned()
+}
+
+/// Cancels a running or queued job. Returns false if there is no such job, or it already finished.
+pub fn cancel(id: JobId) -> bool {
+    let mut jobs = JOBS.lock().unwrap();
+    let Some(job) = jobs.active_mut(id) else {
+        return false;
+    };
+    info!("Cancelling job {}", id);
+    job.progress.cancel();
+
+    true
+}
+
+/// Cancels every running and queued job.
+pub fn cancel_all() {
+    let jobs = JOBS.lock().unwrap();
+    for job in &jobs.active {
+        info!("Cancelling job {}", job.info.id);
+        job.progress.cancel();
+    }
+}
@@ -1,17 +1,47 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod archive_cache;
+mod catalog_cache;
+mod catalog_source;
+mod checksum;
 mod coeiroink_scraping;
+mod coeiroink_version;
 mod commands;
+mod disk_space;
+mod download;
+mod http_client;
+mod install_progress;
+mod install_transaction;
+mod jobs;
+mod link_resolver;
+mod release_manifest;
+mod retry;
+mod settings;
+mod sevenzip;
 
-use coeiroink_scraping::DownloadInfo;
+use coeiroink_scraping::{CatalogQuery, DownloadInfo};
 use tracing::info;
 use tracing::warn;
 
 #[tauri::command]
-async fn fetch_latest_version() -> Result<commands::fetch_latest_version::CheckVersionResult, String>
-{
-    commands::fetch_latest_version::fetch_latest_version()
+async fn fetch_latest_version(
+    app_handle: tauri::AppHandle,
+) -> Result<commands::fetch_latest_version::CheckVersionResult, String> {
+    commands::fetch_latest_version::fetch_latest_version(app_handle)
+        .await
+        .map_err(|e| {
+            warn!("{:?}", e);
+            e.to_string()
+        })
+}
+
+#[tauri::command]
+async fn fetch_coeiroink_versions(
+    app_handle: tauri::AppHandle,
+    query: Option<CatalogQuery>,
+) -> Result<Vec<DownloadInfo>, String> {
+    crate::coeiroink_scraping::query_downloads(app_handle, &query.unwrap_or_default())
         .await
         .map_err(|e| {
             warn!("{:?}", e);
@@ -20,8 +50,25 @@ async fn fetch_latest_version() -> Result<commands::fetch_latest_version::CheckV
 }
 
 #[tauri::command]
-async fn fetch_coeiroink_versions() -> Result<Vec<DownloadInfo>, String> {
-    crate::coeiroink_scraping::fetch_downloads()
+async fn refresh_coeiroink_versions(
+    app_handle: tauri::AppHandle,
+    query: Option<CatalogQuery>,
+) -> Result<Vec<DownloadInfo>, String> {
+    crate::coeiroink_scraping::refresh_downloads(app_handle)
+        .await
+        .map(|downloads| query.unwrap_or_default().filter(downloads))
+        .map_err(|e| {
+            warn!("{:?}", e);
+            e.to_string()
+        })
+}
+
+#[tauri::command]
+async fn fetch_release_notes(
+    app_handle: tauri::AppHandle,
+    version: coeiroink_version::CoeiroinkVersion,
+) -> Result<commands::fetch_release_notes::ReleaseChanges, String> {
+    commands::fetch_release_notes::fetch_release_notes(app_handle, version)
         .await
         .map_err(|e| {
             warn!("{:?}", e);
@@ -41,52 +88,204 @@ async fn get_coeiroink_version(
         })
 }
 
-static ABORT_INSTALL: once_cell::sync::Lazy<tokio::sync::Mutex<Option<tokio::task::AbortHandle>>> =
-    once_cell::sync::Lazy::new(|| tokio::sync::Mutex::new(None));
+/// Queues an install, or any other job that changes an install, and waits for it to finish.
+async fn run_install<T, F>(
+    app_handle: tauri::AppHandle,
+    kind: jobs::JobKind,
+    description: String,
+    install: impl FnOnce(install_progress::InstallProgress) -> F + Send + 'static,
+) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: std::future::Future<Output = anyhow::Result<T>> + Send + 'static,
+{
+    jobs::run(app_handle, kind, description, install)
+        .await
+        .map_err(|e| {
+            if install_progress::is_cancelled(&e) {
+                info!("Installation cancelled");
+            } else {
+                warn!("{:?}", e);
+            }
+            e.to_string()
+        })
+}
 
+/// Queues an install and returns its job ID without waiting for it. How it ends is reported by
+/// `job_updated`.
 #[tauri::command]
 async fn install_coeiroink(
     app_handle: tauri::AppHandle,
     params: commands::install_coeiroink::InstallParams,
-) -> Result<(), String> {
-    {
-        if let Some(abort_handle) = ABORT_INSTALL.lock().await.as_ref() {
-            if !abort_handle.is_finished() {
-                return Err("Installation already in progress".to_string());
-            }
-        }
-    }
-    let task = tokio::task::spawn(commands::install_coeiroink::install_coeiroink(
-        app_handle, params,
-    ));
-    {
-        let mut guard = ABORT_INSTALL.lock().await;
-        *guard = Some(task.abort_handle());
-    }
-
-    let result = task
-        .await
-        .map_err(|e| e.to_string())?
-        .map_err(|e| e.to_string());
+) -> jobs::JobId {
+    let description = format!(
+        "{} ({:?}) -> {}",
+        params.version, params.edition, params.path
+    );
+    let (id, _) = jobs::queue(
+        app_handle.clone(),
+        jobs::JobKind::Install,
+        description,
+        |progress| async move {
+            commands::install_coeiroink::install_coeiroink(app_handle, params, progress)
+                .await
+                .inspect_err(|e| {
+                    if install_progress::is_cancelled(e) {
+                        info!("Installation cancelled");
+                    } else {
+                        warn!("{:?}", e);
+                    }
+                })
+        },
+    );
 
-    {
-        let mut guard = ABORT_INSTALL.lock().await;
-        *guard = None;
-    }
+    id
+}
+
+#[tauri::command]
+async fn install_local_coeiroink(
+    app_handle: tauri::AppHandle,
+    params: commands::install_local_coeiroink::LocalInstallParams,
+) -> Result<commands::get_coeiroink_version::VersionInfo, String> {
+    let description = format!("{} -> {}", params.archive_path, params.path);
+    run_install(
+        app_handle.clone(),
+        jobs::JobKind::LocalInstall,
+        description,
+        |progress| {
+            commands::install_local_coeiroink::install_local_coeiroink(app_handle, params, progress)
+        },
+    )
+    .await
+}
+
+#[tauri::command]
+async fn uninstall_coeiroink(
+    app_handle: tauri::AppHandle,
+    params: commands::uninstall_coeiroink::UninstallParams,
+) -> Result<commands::uninstall_coeiroink::UninstallResult, String> {
+    let description = params
+        .path
+        .clone()
+        .unwrap_or_else(|| "coeiroink_root".to_string());
+    run_install(
+        app_handle.clone(),
+        jobs::JobKind::Uninstall,
+        description,
+        |progress| commands::uninstall_coeiroink::uninstall_coeiroink(app_handle, params, progress),
+    )
+    .await
+}
 
-    result
+/// Checks an install against its archive, and with `repair` re-extracts the broken files.
+#[tauri::command]
+async fn verify_coeiroink(
+    app_handle: tauri::AppHandle,
+    params: commands::verify_coeiroink::VerifyParams,
+) -> Result<commands::verify_coeiroink::VerifyReport, String> {
+    let kind = if params.repair {
+        jobs::JobKind::Repair
+    } else {
+        jobs::JobKind::Verify
+    };
+    let description = params
+        .path
+        .clone()
+        .unwrap_or_else(|| "coeiroink_root".to_string());
+    run_install(app_handle.clone(), kind, description, |progress| {
+        commands::verify_coeiroink::verify_coeiroink(app_handle, params, progress)
+    })
+    .await
 }
 
+/// Asks every running and queued job to stop. They clean up and roll back before emitting
+/// `Cancelled`.
 #[tauri::command]
 async fn cancel_install_coeiroink() -> Result<(), String> {
-    let mut guard = ABORT_INSTALL.lock().await;
-    if let Some(abort_handle) = guard.take() {
-        abort_handle.abort();
-    }
+    jobs::cancel_all();
 
     Ok(())
 }
 
+/// Running and queued jobs first, then finished ones, newest first.
+#[tauri::command]
+async fn list_jobs(app_handle: tauri::AppHandle) -> Vec<jobs::JobInfo> {
+    jobs::list(&app_handle)
+}
+
+#[tauri::command]
+async fn get_job(app_handle: tauri::AppHandle, id: jobs::JobId) -> Option<jobs::JobInfo> {
+    jobs::get(&app_handle, id)
+}
+
+/// Returns false if the job is not running or queued.
+#[tauri::command]
+async fn cancel_job(id: jobs::JobId) -> bool {
+    jobs::cancel(id)
+}
+
+#[tauri::command]
+async fn check_disk_space(
+    app_handle: tauri::AppHandle,
+    params: commands::install_coeiroink::InstallParams,
+) -> Result<Vec<disk_space::VolumeSpace>, String> {
+    commands::check_disk_space::check_disk_space(app_handle, params)
+        .await
+        .map_err(|e| {
+            warn!("{:?}", e);
+            e.to_string()
+        })
+}
+
+#[tauri::command]
+async fn list_archive_cache(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<archive_cache::CachedArchive>, String> {
+    archive_cache::list(&app_handle).await.map_err(|e| {
+        warn!("{:?}", e);
+        e.to_string()
+    })
+}
+
+#[tauri::command]
+async fn purge_archive_cache(
+    app_handle: tauri::AppHandle,
+    version: Option<coeiroink_version::CoeiroinkVersion>,
+    edition: Option<coeiroink_scraping::Edition>,
+) -> Result<u64, String> {
+    let description = format!(
+        "{} ({})",
+        version.as_ref().map_or("*".to_string(), |v| v.to_string()),
+        edition.as_ref().map_or("*".to_string(), |e| e.to_string())
+    );
+    // Queued like the installs, so that it never removes an archive one of them is using.
+    run_install(
+        app_handle.clone(),
+        jobs::JobKind::PurgeArchiveCache,
+        description,
+        |_| async move {
+            archive_cache::purge(&app_handle, version.as_ref(), edition.as_ref()).await
+        },
+    )
+    .await
+}
+
+#[tauri::command]
+async fn get_network_settings(app_handle: tauri::AppHandle) -> http_client::NetworkSettings {
+    http_client::configured_settings(&app_handle)
+}
+
+#[tauri::command]
+async fn set_network_settings(
+    app_handle: tauri::AppHandle,
+    settings: http_client::NetworkSettings,
+) -> Result<(), String> {
+    http_client::save_settings(&app_handle, settings).map_err(|e| {
+        warn!("{:?}", e);
+        e.to_string()
+    })
+}
+
 #[tauri::command]
 async fn default_install_path_root() -> String {
     let install_dir =
@@ -131,11 +330,24 @@ pub fn run() {
             fetch_latest_version,
             get_coeiroink_version,
             install_coeiroink,
+            install_local_coeiroink,
+            uninstall_coeiroink,
+            verify_coeiroink,
             cancel_install_coeiroink,
+            list_jobs,
+            get_job,
+            cancel_job,
+            check_disk_space,
             default_install_path_root,
             fetch_coeiroink_versions,
+            refresh_coeiroink_versions,
+            fetch_release_notes,
             is_safe_to_install,
-            is_coeiroink_dir
+            is_coeiroink_dir,
+            list_archive_cache,
+            purge_archive_cache,
+            get_network_settings,
+            set_network_settings
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
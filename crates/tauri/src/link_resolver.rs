@@ -0,0 +1,214 @@
+use anyhow::Result;
+use tracing::info;
+
+static MAX_REDIRECTS: usize = 10;
+
+static ZIP_MAGICS: &[&[u8]] = &[b"PK\x03\x04", b"PK\x05\x06", b"PK\x07\x08"];
+
+#[derive(Debug)]
+pub enum LinkError {
+    TooManyRedirects { url: String },
+    HtmlPage { url: String, content_type: String },
+    NotAnArchive { url: String, head: Vec<u8> },
+}
+
+impl std::fmt::Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkError::TooManyRedirects { url } => {
+                write!(f, "Too many redirects while resolving {}", url)
+            }
+            LinkError::HtmlPage { url, content_type } => write!(
+                f,
+                "{} returned a web page ({}) instead of an archive; the link may have expired or \
+                 require a login",
+                url, content_type
+            ),
+            LinkError::NotAnArchive { url, head } => write!(
+                f,
+                "{} did not return a ZIP archive (starts with {:02x?})",
+                url, head
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// Rewrites Dropbox share links (`dl=0`, or no `dl` at all) to their direct-download form. Other
+/// links are returned as-is.
+pub fn normalize_link(url: &str) -> Result<reqwest::Url> {
+    let mut url = reqwest::Url::parse(url)?;
+
+    let is_dropbox = url
+        .host_str()
+        .is_some_and(|host| host == "dropbox.com" || host.ends_with(".dropbox.com"));
+    if !is_dropbox {
+        return Ok(url);
+    }
+
+    let query = url
+        .query_pairs()
+        .filter(|(key, _)| key != "dl" && key != "raw")
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect::<Vec<_>>();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(query)
+        .append_pair("dl", "1");
+
+    Ok(url)
+}
+
+pub struct ResolvedLink {
+    pub url: reqwest::Url,
+    pub redirects: Vec<reqwest::Url>,
+    pub response: reqwest::Response,
+}
+
+impl ResolvedLink {
+    pub fn final_url(&self) -> &reqwest::Url {
+        self.redirects.last().unwrap_or(&self.url)
+    }
+
+    /// Fails if the response is obviously a web page, e.g. Dropbox's "this link has expired".
+    pub fn check_content_type(&self) -> Result<(), LinkError> {
+        let content_type = self
+            .response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        if content_type.starts_with("text/html") {
+            return Err(LinkError::HtmlPage {
+                url: self.final_url().to_string(),
+                content_type: content_type.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Sends a GET request to the normalized link, following redirects by hand so they can be logged.
+/// `headers` are sent with every hop.
+pub async fn resolve(
+    client: &crate::http_client::HttpClient,
+    url: &str,
+    headers: reqwest::header::HeaderMap,
+) -> Result<ResolvedLink> {
+    let url = normalize_link(url)?;
+
+    let mut redirects: Vec<reqwest::Url> = vec![];
+    loop {
+        let current = redirects.last().unwrap_or(&url);
+        let response = client
+            .no_redirect
+            .get(current.clone())
+            .headers(headers.clone())
+            .send()
+            .await?;
+
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok());
+        let (true, Some(location)) = (response.status().is_redirection(), location) else {
+            // A 416 answers the `Range` of a resumed download; the caller decides what it means.
+            let response = if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+                response
+            } else {
+                response.error_for_status()?
+            };
+            info!(
+                "Resolved {} to {} ({} redirects)",
+                url,
+                current,
+                redirects.len()
+            );
+            return Ok(ResolvedLink {
+                url,
+                redirects,
+                response,
+            });
+        };
+
+        if redirects.len() >= MAX_REDIRECTS {
+            return Err(LinkError::TooManyRedirects {
+                url: url.to_string(),
+            }
+            .into());
+        }
+        let next = current.join(location)?;
+        info!("Redirected: {} -> {}", current, next);
+        redirects.push(next);
+    }
+}
+
+pub fn check_zip_magic(url: &reqwest::Url, head: &[u8]) -> Result<(), LinkError> {
+    if head.len() < 4 || !ZIP_MAGICS.contains(&&head[..4]) {
+        return Err(LinkError::NotAnArchive {
+            url: url.to_string(),
+            head: head[..head.len().min(16)].to_vec(),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_links() {
+        let cases = [
+            (
+                "https://www.dropbox.com/scl/fi/abc/COEIROINK.zip?rlkey=xyz&dl=0",
+                "https://www.dropbox.com/scl/fi/abc/COEIROINK.zip?rlkey=xyz&dl=1",
+            ),
+            (
+                "https://www.dropbox.com/scl/fi/abc/COEIROINK.zip?rlkey=xyz",
+                "https://www.dropbox.com/scl/fi/abc/COEIROINK.zip?rlkey=xyz&dl=1",
+            ),
+            (
+                "https://dropbox.com/s/abc/COEIROINK.zip?raw=1",
+                "https://dropbox.com/s/abc/COEIROINK.zip?dl=1",
+            ),
+            (
+                "https://www.dropbox.com/s/abc/COEIROINK.zip",
+                "https://www.dropbox.com/s/abc/COEIROINK.zip?dl=1",
+            ),
+            (
+                "https://not-dropbox.com/COEIROINK.zip?dl=0",
+                "https://not-dropbox.com/COEIROINK.zip?dl=0",
+            ),
+            (
+                "https://example.com/COEIROINK.zip",
+                "https://example.com/COEIROINK.zip",
+            ),
+        ];
+        for (link, normalized) in cases {
+            assert_eq!(normalize_link(link).unwrap().as_str(), normalized);
+        }
+
+        assert!(normalize_link("not a url").is_err());
+    }
+
+    #[test]
+    fn checks_zip_magic() {
+        let url = reqwest::Url::parse("https://example.com/COEIROINK.zip").unwrap();
+        let cases: [(&[u8], bool); 7] = [
+            (b"PK\x03\x04\x14\x00", true),
+            (b"PK\x05\x06", true),
+            (b"PK\x07\x08", true),
+            (b"PK\x03", false),
+            (b"", false),
+            (b"<!DOCTYPE html>", false),
+            (b"7z\xbc\xaf\x27\x1c", false),
+        ];
+        for (head, is_zip) in cases {
+            assert_eq!(check_zip_magic(&url, head).is_ok(), is_zip, "{:?}", head);
+        }
+    }
+}
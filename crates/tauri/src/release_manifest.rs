@@ -0,0 +1,419 @@
+//! Self-hosted release manifest, for deploying vetted COEIROINK builds without the public
+//! download page.
+//!
+//! ```json
+//! {
+//!   "schemaVersion": 1,
+//!   "releases": [
+//!     {
+//!       "version": "2.3.4",
+//!       "edition": "cpu",
+//!       "os": "windows",
+//!       "urls": ["https://example.com/COEIROINK-CPU-v.2.3.4-Windows.zip"],
+//!       "sha256": "<64 hex digits>",
+//!       "size": 1600000000,
+//!       "releaseDate": "2024-06-01",
+//!       "notes": "..."
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! `releaseDate` and `notes` are optional, everything else is required.
+
+use crate::coeiroink_scraping::{Catalog, DownloadInfo, Edition, Os, ReleaseNotes};
+use crate::coeiroink_version::CoeiroinkVersion;
+use anyhow::Result;
+use tracing::info;
+
+pub static SCHEMA_VERSION: u64 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ManifestLocation {
+    Url(String),
+    Path(std::path::PathBuf),
+}
+
+impl std::fmt::Display for ManifestLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestLocation::Url(url) => f.write_str(url),
+            ManifestLocation::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawManifest {
+    schema_version: u64,
+    releases: Vec<RawRelease>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawRelease {
+    version: String,
+    edition: String,
+    os: String,
+    urls: Vec<String>,
+    sha256: String,
+    size: u64,
+    release_date: Option<String>,
+    notes: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ManifestRelease {
+    pub version: CoeiroinkVersion,
+    pub edition: Edition,
+    pub os: Os,
+    pub urls: Vec<String>,
+    pub sha256: String,
+    pub size: u64,
+    pub release_date: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReleaseManifest {
+    pub releases: Vec<ManifestRelease>,
+}
+
+/// Every problem found in a manifest, with the JSON path it was found at.
+#[derive(Debug)]
+pub struct ManifestError {
+    pub location: String,
+    pub issues: Vec<String>,
+}
+
+impl std::fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid release manifest {}:", self.location)?;
+        for issue in &self.issues {
+            write!(f, "\n- {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+impl ReleaseManifest {
+    pub fn parse(json: &str, location: &str) -> Result<Self, ManifestError> {
+        let raw: RawManifest = serde_json::from_str(json).map_err(|e| ManifestError {
+            location: location.to_string(),
+            issues: vec![e.to_string()],
+        })?;
+
+        let mut issues = vec![];
+        if raw.schema_version != SCHEMA_VERSION {
+            issues.push(format!(
+                "schemaVersion: expected {}, found {}",
+                SCHEMA_VERSION, raw.schema_version
+            ));
+        }
+        if raw.releases.is_empty() {
+            issues.push("releases: must not be empty".to_string());
+        }
+
+        let mut releases: Vec<ManifestRelease> = vec![];
+        for (i, release) in raw.releases.into_iter().enumerate() {
+            let path = format!("releases[{}]", i);
+            let Some(release) = validate_release(release, &path, &mut issues) else {
+                continue;
+            };
+            if releases.iter().any(|r| {
+                r.version == release.version && r.edition == release.edition && r.os == release.os
+            }) {
+                issues.push(format!(
+                    "{}: duplicate release {} {} {}",
+                    path, release.version, release.edition, release.os
+                ));
+                continue;
+            }
+            releases.push(release);
+        }
+
+        if !issues.is_empty() {
+            return Err(ManifestError {
+                location: location.to_string(),
+                issues,
+            });
+        }
+
+        Ok(Self { releases })
+    }
+
+    pub fn to_catalog(&self) -> Catalog {
+        let downloads = self
+            .releases
+            .iter()
+            .map(|r| DownloadInfo {
+                edition: r.edition.clone(),
+                os: r.os.clone(),
+                version: r.version.clone(),
+                link: r.urls[0].clone(),
+                mirrors: r.urls[1..].to_vec(),
+                release_date: r.release_date.clone(),
+                size: Some(r.size),
+                sha256: Some(r.sha256.clone()),
+            })
+            .collect();
+
+        let mut releases: Vec<ReleaseNotes> = vec![];
+        for release in &self.releases {
+            let Some(notes) = &release.notes else {
+                continue;
+            };
+            if releases.iter().any(|r| r.version == release.version) {
+                continue;
+            }
+            releases.push(ReleaseNotes {
+                version: release.version.clone(),
+                release_date: release.release_date.clone(),
+                notes: notes.clone(),
+            });
+        }
+
+        Catalog {
+            downloads,
+            releases,
+        }
+    }
+}
+
+fn validate_release(
+    release: RawRelease,
+    path: &str,
+    issues: &mut Vec<String>,
+) -> Option<ManifestRelease> {
+    let issue_count = issues.len();
+
+    let version = release
+        .version
+        .parse::<CoeiroinkVersion>()
+        .inspect_err(|e| issues.push(format!("{}.version: {}", path, e)))
+        .ok();
+    let edition = release
+        .edition
+        .parse::<Edition>()
+        .inspect_err(|_| {
+            issues.push(format!(
+                "{}.edition: expected \"cpu\" or \"gpu\", found {:?}",
+                path, release.edition
+            ))
+        })
+        .ok();
+    let os = release
+        .os
+        .parse::<Os>()
+        .inspect_err(|_| {
+            issues.push(format!(
+                "{}.os: expected \"windows\" or \"mac\", found {:?}",
+                path, release.os
+            ))
+        })
+        .ok();
+
+    if release.urls.is_empty() {
+        issues.push(format!("{}.urls: must not be empty", path));
+    }
+    for (i, url) in release.urls.iter().enumerate() {
+        match reqwest::Url::parse(url) {
+            Ok(url) if ["http", "https"].contains(&url.scheme()) => {}
+            Ok(url) => issues.push(format!(
+                "{}.urls[{}]: unsupported scheme {:?}",
+                path,
+                i,
+                url.scheme()
+            )),
+            Err(e) => issues.push(format!("{}.urls[{}]: {}", path, i, e)),
+        }
+    }
+
+    if release.sha256.len() != 64 || !release.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+        issues.push(format!(
+            "{}.sha256: expected 64 hex digits, found {:?}",
+            path, release.sha256
+        ));
+    }
+    if release.size == 0 {
+        issues.push(format!("{}.size: must be greater than 0", path));
+    }
+
+    if issues.len() != issue_count {
+        return None;
+    }
+
+    Some(ManifestRelease {
+        version: version?,
+        edition: edition?,
+        os: os?,
+        urls: release.urls,
+        sha256: release.sha256.to_ascii_lowercase(),
+        size: release.size,
+        release_date: release.release_date,
+        notes: release.notes,
+    })
+}
+
+pub async fn load_manifest(
+    client: &crate::http_client::HttpClient,
+    location: &ManifestLocation,
+) -> Result<ReleaseManifest> {
+    info!("Loading release manifest: {}", location);
+    let json = match location {
+        ManifestLocation::Url(url) => {
+            client
+                .client
+                .get(url)
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?
+        }
+        ManifestLocation::Path(path) => fs_err::tokio::read_to_string(path).await?,
+    };
+
+    let manifest = ReleaseManifest::parse(&json, &location.to_string())?;
+    info!("Found {} releases in manifest", manifest.releases.len());
+
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release() -> serde_json::Value {
+        serde_json::json!({
+            "version": "2.3.4",
+            "edition": "cpu",
+            "os": "windows",
+            "urls": ["https://example.com/COEIROINK-CPU-v.2.3.4-Windows.zip"],
+            "sha256": "A".repeat(64),
+            "size": 1600000000u64,
+        })
+    }
+
+    fn issues(manifest: serde_json::Value) -> Vec<String> {
+        ReleaseManifest::parse(&manifest.to_string(), "test.json")
+            .map(|_| vec![])
+            .unwrap_or_else(|e| e.issues)
+    }
+
+    fn release_issues(field: &str, value: serde_json::Value) -> Vec<String> {
+        let mut release = release();
+        release[field] = value;
+
+        issues(serde_json::json!({ "schemaVersion": 1, "releases": [release] }))
+    }
+
+    #[test]
+    fn accepts_valid_manifest() {
+        let manifest = serde_json::json!({ "schemaVersion": 1, "releases": [release()] });
+        let manifest = ReleaseManifest::parse(&manifest.to_string(), "test.json").unwrap();
+
+        assert_eq!(manifest.releases.len(), 1);
+        assert_eq!(manifest.releases[0].sha256, "a".repeat(64));
+    }
+
+    #[test]
+    fn reports_invalid_releases() {
+        let cases = [
+            (
+                "version",
+                serde_json::json!("latest"),
+                "releases[0].version: Invalid COEIROINK version: \"latest\"",
+            ),
+            (
+                "edition",
+                serde_json::json!("tpu"),
+                "releases[0].edition: expected \"cpu\" or \"gpu\", found \"tpu\"",
+            ),
+            (
+                "os",
+                serde_json::json!("linux"),
+                "releases[0].os: expected \"windows\" or \"mac\", found \"linux\"",
+            ),
+            (
+                "urls",
+                serde_json::json!([]),
+                "releases[0].urls: must not be empty",
+            ),
+            (
+                "urls",
+                serde_json::json!(["ftp://example.com/a.zip"]),
+                "releases[0].urls[0]: unsupported scheme \"ftp\"",
+            ),
+            (
+                "sha256",
+                serde_json::json!("abc"),
+                "releases[0].sha256: expected 64 hex digits, found \"abc\"",
+            ),
+            (
+                "size",
+                serde_json::json!(0),
+                "releases[0].size: must be greater than 0",
+            ),
+        ];
+        for (field, value, issue) in cases {
+            assert_eq!(release_issues(field, value), vec![issue], "{}", field);
+        }
+    }
+
+    #[test]
+    fn reports_invalid_manifests() {
+        let cases = [
+            (
+                serde_json::json!({ "schemaVersion": 2, "releases": [release()] }),
+                vec!["schemaVersion: expected 1, found 2"],
+            ),
+            (
+                serde_json::json!({ "schemaVersion": 1, "releases": [] }),
+                vec!["releases: must not be empty"],
+            ),
+            (
+                serde_json::json!({ "schemaVersion": 1, "releases": [release(), release()] }),
+                vec!["releases[1]: duplicate release 2.3.4 Cpu Windows"],
+            ),
+        ];
+        for (manifest, expected) in cases {
+            assert_eq!(issues(manifest.clone()), expected, "{}", manifest);
+        }
+    }
+
+    #[test]
+    fn reports_every_issue() {
+        let mut invalid = release();
+        invalid["os"] = serde_json::json!("linux");
+        invalid["size"] = serde_json::json!(0);
+        let manifest = serde_json::json!({ "schemaVersion": 2, "releases": [release(), invalid] });
+
+        assert_eq!(
+            issues(manifest),
+            vec![
+                "schemaVersion: expected 1, found 2",
+                "releases[1].os: expected \"windows\" or \"mac\", found \"linux\"",
+                "releases[1].size: must be greater than 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let mut release = release();
+        release["checksum"] = serde_json::json!("");
+        let issues = issues(serde_json::json!({ "schemaVersion": 1, "releases": [release] }));
+
+        assert_eq!(issues.len(), 1);
+        assert!(
+            issues[0].contains("unknown field `checksum`"),
+            "{:?}",
+            issues
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use anyhow::Result;
+use tracing::warn;
+
+/// Read from the `retry` key of the store, e.g. `{"maxAttempts": 5, "initialDelayMs": 1000}`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_delay_ms: 1000,
+            max_delay_ms: 30 * 1000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn configured(app_handle: &tauri::AppHandle) -> Self {
+        crate::settings::get(app_handle, "retry")
+            .and_then(|v| {
+                serde_json::from_value(v)
+                    .inspect_err(|e| warn!("Invalid retry policy: {:?}", e))
+                    .ok()
+            })
+            .unwrap_or_default()
+    }
+
+    fn delay(&self, attempt: u32) -> std::time::Duration {
+        let delay = self
+            .initial_delay_ms
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_delay_ms);
+
+        std::time::Duration::from_millis(delay)
+    }
+}
+
+/// Whether retrying could possibly help: network errors, timeouts, 5xx and 429. A 404, an
+/// HTML page instead of an archive or a bad checksum will not fix themselves.
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return match e.status() {
+                Some(status) => {
+                    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                }
+                None => e.is_timeout() || e.is_connect() || e.is_body(),
+            };
+        }
+
+        if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+            // Local errors such as a missing file or a full disk will not fix themselves.
+            return matches!(
+                e.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::UnexpectedEof
+                    | std::io::ErrorKind::Interrupted
+            );
+        }
+
+        cause.is::<crate::download::IncompleteDownload>()
+            || cause.is::<crate::download::RangeNotHonored>()
+    })
+}
+
+/// Runs `operation` until it succeeds, fails with a permanent error, or runs out of attempts,
+/// doubling the delay between attempts.
+pub async fn retry<T, F, Fut>(policy: &RetryPolicy, what: &str, mut operation: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt + 1 < policy.max_attempts && is_transient(&e) => {
+                let delay = policy.delay(attempt);
+                warn!(
+                    "{} failed (attempt {}/{}), retrying in {:?}: {:?}",
+                    what,
+                    attempt + 1,
+                    policy.max_attempts,
+                    delay,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
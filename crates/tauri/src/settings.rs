@@ -0,0 +1,110 @@
+//! `store.json`, which holds `coeiroink_root` and every setting. The backend reads and writes it
+//! through the plugin's `StoreCollection`, the same copy the frontend's `Store` uses, so neither
+//! side overwrites the other's changes when saving.
+
+use anyhow::{Context as _, Result};
+use tauri::Manager;
+use tracing::warn;
+
+static STORE_PATH: &str = "store.json";
+
+fn with_store<R: tauri::Runtime, T>(
+    app_handle: &tauri::AppHandle<R>,
+    f: impl FnOnce(&mut tauri_plugin_store::Store<R>) -> tauri_plugin_store::Result<T>,
+) -> tauri_plugin_store::Result<T> {
+    tauri_plugin_store::with_store(
+        app_handle.clone(),
+        app_handle.state::<tauri_plugin_store::StoreCollection<R>>(),
+        STORE_PATH,
+        f,
+    )
+}
+
+/// Makes sure the store can be updated. The plugin ignores load errors when it first opens the
+/// store, so an empty store with a file behind it is loaded again, and saving is refused if that
+/// fails, since it would lose the settings in the file.
+fn ensure_loaded<R: tauri::Runtime>(
+    app_handle: &tauri::AppHandle<R>,
+    store: &mut tauri_plugin_store::Store<R>,
+) -> tauri_plugin_store::Result<()> {
+    let exists = app_handle
+        .path()
+        .app_data_dir()
+        .is_ok_and(|dir| dir.join(STORE_PATH).exists());
+    if store.is_empty() && exists {
+        store.load()?;
+    }
+
+    Ok(())
+}
+
+pub fn get<R: tauri::Runtime>(
+    app_handle: &tauri::AppHandle<R>,
+    key: &str,
+) -> Option<serde_json::Value> {
+    with_store(app_handle, |store| Ok(store.get(key).cloned()))
+        .inspect_err(|e| warn!("Failed to load the settings: {:?}", e))
+        .ok()
+        .flatten()
+}
+
+pub fn set<R: tauri::Runtime>(
+    app_handle: &tauri::AppHandle<R>,
+    key: &str,
+    value: serde_json::Value,
+) -> Result<()> {
+    with_store(app_handle, |store| {
+        ensure_loaded(app_handle, store)?;
+        store.insert(key.to_string(), value)?;
+        store.save()
+    })
+    .context("Failed to save the settings")
+}
+
+pub fn delete<R: tauri::Runtime>(app_handle: &tauri::AppHandle<R>, key: &str) -> Result<()> {
+    with_store(app_handle, |store| {
+        ensure_loaded(app_handle, store)?;
+        store.delete(key)?;
+        store.save()
+    })
+    .context("Failed to save the settings")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_app() -> tauri::App<tauri::test::MockRuntime> {
+        let mut context = tauri::test::mock_context(tauri::test::noop_assets());
+        context.config_mut().identifier = format!("coeirobottle.test.{}", std::process::id());
+        tauri::test::mock_builder()
+            .plugin(tauri_plugin_store::Builder::new().build())
+            .build(context)
+            .unwrap()
+    }
+
+    #[test]
+    fn deleted_key_stays_gone_after_frontend_save() {
+        let app = mock_app();
+        let app_handle = app.handle().clone();
+
+        set(&app_handle, "coeiroink_root", "C:\\COEIROINK".into()).unwrap();
+        assert!(get(&app_handle, "coeiroink_root").is_some());
+
+        delete(&app_handle, "coeiroink_root").unwrap();
+
+        // What the frontend's `store.set()` and `store.save()` do.
+        with_store(&app_handle, |store| {
+            store.insert("other".to_string(), true.into())?;
+            store.save()
+        })
+        .unwrap();
+
+        let mut saved = tauri_plugin_store::StoreBuilder::new(STORE_PATH).build(app_handle.clone());
+        saved.load().unwrap();
+        assert_eq!(saved.get("coeiroink_root"), None);
+        assert_eq!(saved.get("other"), Some(&serde_json::Value::Bool(true)));
+
+        fs_err::remove_dir_all(app_handle.path().app_data_dir().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,838 @@
+use crate::install_progress::Cancelled;
+use anyhow::{bail, Result};
+use std::io::{Read, Seek};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// One file or directory in an archive.
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub path: String,
+    /// Uncompressed size in bytes.
+    pub size: u64,
+    pub is_dir: bool,
+    pub crc: Option<u32>,
+    pub modified: Option<std::time::SystemTime>,
+}
+
+impl ArchiveEntry {
+    /// Whether any component of the path is `name`.
+    pub fn is_under(&self, name: &str) -> bool {
+        self.path.split(['/', '\\']).any(|c| c == name)
+    }
+
+    /// Where the entry ends up when extracted into `root`.
+    pub fn local_path(&self, root: &std::path::Path) -> std::path::PathBuf {
+        self.path
+            .split(['/', '\\'])
+            .filter(|c| !c.is_empty())
+            .fold(root.to_owned(), |path, c| path.join(c))
+    }
+
+    /// Whether `path` has the size and contents of this entry. Unless `thorough` is set, the CRC
+    /// is only computed when the modification time differs from the archive, so files that were
+    /// just extracted, and thus have the archive's time, must be checked thoroughly.
+    pub fn matches(&self, path: &std::path::Path, thorough: bool) -> Result<bool> {
+        let metadata = match fs_err::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        if self.is_dir || metadata.is_dir() {
+            return Ok(self.is_dir == metadata.is_dir());
+        }
+        if metadata.len() != self.size {
+            return Ok(false);
+        }
+        let Some(crc) = self.crc else {
+            return Ok(true);
+        };
+        if !thorough && self.modified.is_some() && metadata.modified().ok() == self.modified {
+            return Ok(true);
+        }
+
+        let mut file = fs_err::File::open(path)?;
+        let mut hasher = crc32fast::Hasher::new();
+        let mut buffer = vec![0; 1024 * 1024];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+
+        Ok(hasher.finalize() == crc)
+    }
+}
+
+/// Lists, tests and extracts the 7z volume sets that COEIROINK is distributed as. `first_volume` is
+/// the `.001` file; the rest of the set is found next to it.
+pub trait SevenZipBackend {
+    async fn list(&self, first_volume: &std::path::Path) -> Result<Vec<ArchiveEntry>>;
+
+    /// Fails if any entry is damaged or a volume is missing.
+    async fn test(&self, first_volume: &std::path::Path, cancel: &CancellationToken) -> Result<()>;
+
+    /// Extracts everything into `out_dir`, skipping entries with a path component in `exclude`,
+    /// and calls `on_entry` with the path of each extracted file.
+    ///
+    /// Once `cancel` fires, fails with [`Cancelled`] after nothing is writing to `out_dir` anymore.
+    async fn extract(
+        &self,
+        first_volume: &std::path::Path,
+        out_dir: &std::path::Path,
+        exclude: &[&str],
+        cancel: &CancellationToken,
+        on_entry: impl FnMut(&str) -> Result<()> + Send + 'static,
+    ) -> Result<()>;
+
+    /// Like [`SevenZipBackend::extract`], but only the files at `paths`, as listed by
+    /// [`SevenZipBackend::list`].
+    async fn extract_only(
+        &self,
+        first_volume: &std::path::Path,
+        out_dir: &std::path::Path,
+        paths: &[String],
+        cancel: &CancellationToken,
+        on_entry: impl FnMut(&str) -> Result<()> + Send + 'static,
+    ) -> Result<()>;
+}
+
+/// The bundled (or a user-provided) `7zr` executable.
+pub struct External {
+    pub path: std::path::PathBuf,
+}
+
+impl External {
+    fn command(&self) -> Result<tokio::process::Command> {
+        if !self.path.exists() {
+            bail!(
+                "7-Zip not found at {}; use the native 7z backend instead",
+                self.path.display()
+            );
+        }
+
+        let mut command = tokio::process::Command::new(&self.path);
+        command.kill_on_drop(true);
+
+        Ok(command)
+    }
+
+    fn extract_command(&self, out_dir: &std::path::Path) -> Result<tokio::process::Command> {
+        let mut command = self.command()?;
+        command
+            .arg("x")
+            .arg(format!("-o{}", out_dir.to_string_lossy()))
+            .arg("-y")
+            .arg("-bb3")
+            // Reported paths must match the ones in the listing, which is also UTF-8.
+            .arg("-sccUTF-8");
+
+        Ok(command)
+    }
+}
+
+/// Waits for `child` while `output` handles its output. If `cancel` fires or `output` fails, the
+/// process is killed and waited for, so that it no longer holds on to any files.
+async fn supervise(
+    mut child: tokio::process::Child,
+    cancel: &CancellationToken,
+    output: impl std::future::Future<Output = Result<()>>,
+) -> Result<std::process::ExitStatus> {
+    let cancelled = std::pin::pin!(cancel.cancelled());
+    let result = match futures_util::future::select(cancelled, std::pin::pin!(output)).await {
+        futures_util::future::Either::Left(_) => Err(Cancelled.into()),
+        futures_util::future::Either::Right((result, _)) => result,
+    };
+    if let Err(e) = result {
+        info!("Stopping 7-Zip");
+        if let Err(kill_error) = child.kill().await {
+            warn!("Failed to stop 7-Zip: {:?}", kill_error);
+        }
+        return Err(e);
+    }
+
+    Ok(child.wait().await?)
+}
+
+/// Runs a `7z x` command, calling `on_entry` for each file it reports.
+async fn run_extract(
+    mut command: tokio::process::Command,
+    cancel: &CancellationToken,
+    mut on_entry: impl FnMut(&str) -> Result<()> + Send + 'static,
+) -> Result<()> {
+    let mut extract_process = command.stdout(std::process::Stdio::piped()).spawn()?;
+
+    let mut extract_stdout = tokio::io::BufReader::new(extract_process.stdout.take().unwrap());
+
+    let status = supervise(extract_process, cancel, async {
+        let mut line = String::new();
+        while extract_stdout.read_line(&mut line).await? > 0 {
+            if let Some(path) = line.strip_prefix("- ") {
+                on_entry(path.trim())?;
+            }
+            line.clear();
+        }
+        Ok(())
+    })
+    .await?;
+
+    if !status.success() {
+        bail!("Failed to extract 7z");
+    }
+
+    Ok(())
+}
+
+/// `Modified` is printed in local time, e.g. `2024-05-01 12:34:56.1234567`.
+fn parse_modified(value: &str) -> Option<std::time::SystemTime> {
+    let naive = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f").ok()?;
+    let local = naive.and_local_timezone(chrono::Local).earliest()?;
+
+    Some(local.into())
+}
+
+/// Parses the output of `7z l -slt`: after the archive headers and a `----------` line, each entry
+/// is a block of `Key = Value` lines separated by blank lines.
+fn parse_technical_listing(stdout: &str) -> Result<Vec<ArchiveEntry>> {
+    let mut lines = stdout.lines().map(|line| line.trim_end_matches('\r'));
+    if !lines.any(|line| line == "----------") {
+        bail!("Unexpected 7z listing: no entries found");
+    }
+
+    let mut entries = vec![];
+    let mut properties = std::collections::HashMap::<&str, &str>::new();
+    for line in lines.chain([""]) {
+        if line.is_empty() {
+            if properties.is_empty() {
+                continue;
+            }
+            let Some(path) = properties.remove("Path") else {
+                bail!("Unexpected 7z listing: entry without a path");
+            };
+            let size = match properties.get("Size") {
+                Some(size) if !size.is_empty() => size
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid size of {}: {}", path, size))?,
+                _ => 0,
+            };
+            let is_dir = properties.get("Folder") == Some(&"+")
+                || properties
+                    .get("Attributes")
+                    .and_then(|attributes| attributes.split(' ').next())
+                    .is_some_and(|attributes| attributes.contains('D'));
+            let crc = properties
+                .get("CRC")
+                .filter(|crc| !crc.is_empty())
+                .map(|crc| u32::from_str_radix(crc, 16))
+                .transpose()
+                .map_err(|_| anyhow::anyhow!("Invalid CRC of {}", path))?;
+            let modified = properties
+                .get("Modified")
+                .and_then(|modified| parse_modified(modified));
+
+            entries.push(ArchiveEntry {
+                path: path.to_owned(),
+                size,
+                is_dir,
+                crc,
+                modified,
+            });
+            properties.clear();
+            continue;
+        }
+
+        // Values are not trimmed: file names may start with a space.
+        let property = line
+            .split_once(" = ")
+            .or_else(|| line.strip_suffix(" =").map(|key| (key, "")));
+        if let Some((key, value)) = property {
+            properties.insert(key, value);
+        }
+    }
+
+    Ok(entries)
+}
+
+impl SevenZipBackend for External {
+    async fn list(&self, first_volume: &std::path::Path) -> Result<Vec<ArchiveEntry>> {
+        let files = self
+            .command()?
+            .arg("l")
+            .arg("-slt")
+            .arg("-sccUTF-8")
+            .arg(first_volume)
+            .output()
+            .await?;
+
+        if !files.status.success() {
+            bail!("Failed to list files in 7z");
+        }
+
+        parse_technical_listing(&String::from_utf8_lossy(&files.stdout))
+    }
+
+    async fn test(&self, first_volume: &std::path::Path, cancel: &CancellationToken) -> Result<()> {
+        let mut test_process = self
+            .command()?
+            .arg("t")
+            .arg(first_volume)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .spawn()?;
+
+        let mut stderr = String::new();
+        let mut test_stderr = test_process.stderr.take().unwrap();
+        let status = supervise(test_process, cancel, async {
+            test_stderr.read_to_string(&mut stderr).await?;
+            Ok(())
+        })
+        .await?;
+
+        if !status.success() {
+            bail!("The archive is damaged: {}", stderr.trim());
+        }
+
+        Ok(())
+    }
+
+    async fn extract(
+        &self,
+        first_volume: &std::path::Path,
+        out_dir: &std::path::Path,
+        exclude: &[&str],
+        cancel: &CancellationToken,
+        on_entry: impl FnMut(&str) -> Result<()> + Send + 'static,
+    ) -> Result<()> {
+        let mut command = self.extract_command(out_dir)?;
+        command
+            .args(exclude.iter().map(|name| format!("-xr!{}", name)))
+            .arg(first_volume);
+
+        run_extract(command, cancel, on_entry).await
+    }
+
+    async fn extract_only(
+        &self,
+        first_volume: &std::path::Path,
+        out_dir: &std::path::Path,
+        paths: &[String],
+        cancel: &CancellationToken,
+        on_entry: impl FnMut(&str) -> Result<()> + Send + 'static,
+    ) -> Result<()> {
+        // Too many paths for a command line; 7-Zip reads them from a list file instead.
+        let list = tempfile::NamedTempFile::new()?;
+        fs_err::write(list.path(), paths.join("\n"))?;
+
+        let mut command = self.extract_command(out_dir)?;
+        command
+            .arg("-scsUTF-8")
+            .arg(first_volume)
+            .arg(format!("@{}", list.path().to_string_lossy()));
+
+        run_extract(command, cancel, on_entry).await
+    }
+}
+
+/// Presents the volumes `name.7z.001`, `name.7z.002`, ... as one continuous file.
+struct MultiVolumeReader {
+    volumes: Vec<fs_err::File>,
+    /// Offset of each volume in the combined stream.
+    starts: Vec<u64>,
+    len: u64,
+    position: u64,
+}
+
+impl MultiVolumeReader {
+    fn open(first_volume: &std::path::Path) -> Result<Self> {
+        let mut paths = vec![first_volume.to_owned()];
+        if let Some(base) = first_volume.to_string_lossy().strip_suffix(".001") {
+            for i in 2.. {
+                let path = std::path::PathBuf::from(format!("{}.{:03}", base, i));
+                if !path.exists() {
+                    break;
+                }
+                paths.push(path);
+            }
+        }
+        info!("Found {} 7z volumes", paths.len());
+
+        let mut volumes = vec![];
+        let mut starts = vec![];
+        let mut len = 0;
+        for path in paths {
+            let volume = fs_err::File::open(path)?;
+            starts.push(len);
+            len += volume.metadata()?.len();
+            volumes.push(volume);
+        }
+
+        Ok(Self {
+            volumes,
+            starts,
+            len,
+            position: 0,
+        })
+    }
+}
+
+impl Read for MultiVolumeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.position >= self.len {
+            return Ok(0);
+        }
+        let index = self.starts.partition_point(|start| *start <= self.position) - 1;
+        let end = self.starts.get(index + 1).copied().unwrap_or(self.len);
+
+        let volume = &mut self.volumes[index];
+        volume.seek(std::io::SeekFrom::Start(self.position - self.starts[index]))?;
+        let max = buf.len().min((end - self.position) as usize);
+        let read = volume.read(&mut buf[..max])?;
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl Seek for MultiVolumeReader {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            std::io::SeekFrom::Start(offset) => Some(offset),
+            std::io::SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            std::io::SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        let Some(position) = position else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Seek before the start of the archive",
+            ));
+        };
+        self.position = position;
+
+        Ok(position)
+    }
+}
+
+/// Path components of an entry, which may use either separator. `None` for anything that could
+/// escape the output directory.
+fn entry_components(name: &str) -> Option<Vec<&str>> {
+    let components = name
+        .split(['/', '\\'])
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect::<Vec<_>>();
+    if components.is_empty() || components.iter().any(|c| *c == ".." || c.contains(':')) {
+        return None;
+    }
+
+    Some(components)
+}
+
+/// Fails reads once `cancel` fires, so that a large entry stops part way through.
+struct CancellableReader<'a> {
+    inner: &'a mut dyn Read,
+    cancel: &'a CancellationToken,
+}
+
+impl Read for CancellableReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.cancel.is_cancelled() {
+            return Err(std::io::Error::other(Cancelled));
+        }
+
+        self.inner.read(buf)
+    }
+}
+
+/// Writes one entry below `out_dir` if `wanted` accepts its path components. Returns whether a
+/// file was written.
+fn extract_entry(
+    entry: &sevenz_rust2::ArchiveEntry,
+    data: &mut dyn Read,
+    out_dir: &std::path::Path,
+    wanted: &dyn Fn(&[&str]) -> bool,
+) -> Result<bool> {
+    let Some(components) = entry_components(&entry.name) else {
+        bail!("Invalid path: {}", entry.name);
+    };
+    if entry.is_anti_item || !wanted(&components) {
+        // Entries of a solid block have to be read through to reach the next one.
+        std::io::copy(data, &mut std::io::sink())?;
+        return Ok(false);
+    }
+
+    let path = components
+        .iter()
+        .fold(out_dir.to_owned(), |path, c| path.join(c));
+    if entry.is_directory {
+        fs_err::create_dir_all(&path)?;
+        return Ok(false);
+    }
+    fs_err::create_dir_all(path.parent().unwrap())?;
+    let mut file = fs_err::File::create(&path)?;
+    std::io::copy(data, &mut file)?;
+    if entry.has_last_modified_date {
+        file.file().set_modified(entry.last_modified_date.into())?;
+    }
+
+    Ok(true)
+}
+
+/// In-process extraction with `sevenz-rust2`; works without any external executable.
+pub struct Native;
+
+impl Native {
+    fn open(
+        first_volume: &std::path::Path,
+    ) -> Result<sevenz_rust2::ArchiveReader<MultiVolumeReader>> {
+        Ok(sevenz_rust2::ArchiveReader::new(
+            MultiVolumeReader::open(first_volume)?,
+            sevenz_rust2::Password::empty(),
+        )?)
+    }
+
+    async fn extract_matching(
+        first_volume: &std::path::Path,
+        out_dir: &std::path::Path,
+        cancel: &CancellationToken,
+        wanted: impl Fn(&[&str]) -> bool + Send + 'static,
+        mut on_entry: impl FnMut(&str) -> Result<()> + Send + 'static,
+    ) -> Result<()> {
+        let first_volume = first_volume.to_owned();
+        let out_dir = out_dir.to_owned();
+        let cancel = cancel.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut reader = Self::open(&first_volume)?;
+            let mut callback_error = None;
+            let result = reader.for_each_entries(|entry, data| {
+                let mut data = CancellableReader {
+                    inner: data,
+                    cancel: &cancel,
+                };
+                let extracted =
+                    extract_entry(entry, &mut data, &out_dir, &wanted).and_then(|extracted| {
+                        match extracted {
+                            true => on_entry(&entry.name),
+                            false => Ok(()),
+                        }
+                    });
+                match extracted {
+                    Ok(()) => Ok(true),
+                    Err(e) => {
+                        callback_error = Some(e);
+                        Ok(false)
+                    }
+                }
+            });
+
+            if cancel.is_cancelled() {
+                return Err(Cancelled.into());
+            }
+            if let Some(e) = callback_error {
+                return Err(e);
+            }
+            result.map_err(|e| anyhow::anyhow!("Failed to extract 7z: {}", e))
+        })
+        .await?
+    }
+}
+
+impl SevenZipBackend for Native {
+    async fn list(&self, first_volume: &std::path::Path) -> Result<Vec<ArchiveEntry>> {
+        let first_volume = first_volume.to_owned();
+        tokio::task::spawn_blocking(move || {
+            let reader = Self::open(&first_volume)?;
+
+            Ok(reader
+                .archive()
+                .files
+                .iter()
+                .filter(|entry| !entry.is_anti_item)
+                .map(|entry| ArchiveEntry {
+                    path: entry.name.clone(),
+                    size: entry.size,
+                    is_dir: entry.is_directory,
+                    crc: entry.has_crc.then_some(entry.crc as u32),
+                    modified: entry
+                        .has_last_modified_date
+                        .then(|| entry.last_modified_date.into()),
+                })
+                .collect())
+        })
+        .await?
+    }
+
+    async fn test(&self, first_volume: &std::path::Path, cancel: &CancellationToken) -> Result<()> {
+        let first_volume = first_volume.to_owned();
+        let cancel = cancel.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut reader = Self::open(&first_volume)?;
+            // Reading an entry to the end verifies its CRC.
+            let result = reader.for_each_entries(|_, data| {
+                let mut data = CancellableReader {
+                    inner: data,
+                    cancel: &cancel,
+                };
+                std::io::copy(&mut data, &mut std::io::sink())?;
+                Ok(true)
+            });
+
+            if cancel.is_cancelled() {
+                return Err(Cancelled.into());
+            }
+            result.map_err(|e| anyhow::anyhow!("The archive is damaged: {}", e))
+        })
+        .await?
+    }
+
+    async fn extract(
+        &self,
+        first_volume: &std::path::Path,
+        out_dir: &std::path::Path,
+        exclude: &[&str],
+        cancel: &CancellationToken,
+        on_entry: impl FnMut(&str) -> Result<()> + Send + 'static,
+    ) -> Result<()> {
+        let exclude = exclude.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        let wanted =
+            move |components: &[&str]| !components.iter().any(|c| exclude.iter().any(|e| e == c));
+
+        Self::extract_matching(first_volume, out_dir, cancel, wanted, on_entry).await
+    }
+
+    async fn extract_only(
+        &self,
+        first_volume: &std::path::Path,
+        out_dir: &std::path::Path,
+        paths: &[String],
+        cancel: &CancellationToken,
+        on_entry: impl FnMut(&str) -> Result<()> + Send + 'static,
+    ) -> Result<()> {
+        let paths = paths
+            .iter()
+            .filter_map(|path| entry_components(path).map(|c| c.join("/")))
+            .collect::<std::collections::HashSet<_>>();
+        let wanted = move |components: &[&str]| paths.contains(&components.join("/"));
+
+        Self::extract_matching(first_volume, out_dir, cancel, wanted, on_entry).await
+    }
+}
+
+/// Read from the `sevenzip_backend` key of the store, e.g. `{"type": "external"}`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SevenZipConfig {
+    #[default]
+    Native,
+    External {
+        /// Defaults to the `7zr.exe` bundled next to the executable.
+        #[serde(default)]
+        path: Option<std::path::PathBuf>,
+    },
+}
+
+impl SevenZipConfig {
+    fn external(path: &Option<std::path::PathBuf>) -> External {
+        External {
+            path: path.clone().unwrap_or_else(assets::sevenzip_path),
+        }
+    }
+}
+
+impl SevenZipBackend for SevenZipConfig {
+    async fn list(&self, first_volume: &std::path::Path) -> Result<Vec<ArchiveEntry>> {
+        match self {
+            SevenZipConfig::Native => Native.list(first_volume).await,
+            SevenZipConfig::External { path } => Self::external(path).list(first_volume).await,
+        }
+    }
+
+    async fn test(&self, first_volume: &std::path::Path, cancel: &CancellationToken) -> Result<()> {
+        match self {
+            SevenZipConfig::Native => Native.test(first_volume, cancel).await,
+            SevenZipConfig::External { path } => {
+                Self::external(path).test(first_volume, cancel).await
+            }
+        }
+    }
+
+    async fn extract(
+        &self,
+        first_volume: &std::path::Path,
+        out_dir: &std::path::Path,
+        exclude: &[&str],
+        cancel: &CancellationToken,
+        on_entry: impl FnMut(&str) -> Result<()> + Send + 'static,
+    ) -> Result<()> {
+        match self {
+            SevenZipConfig::Native => {
+                Native
+                    .extract(first_volume, out_dir, exclude, cancel, on_entry)
+                    .await
+            }
+            SevenZipConfig::External { path } => {
+                Self::external(path)
+                    .extract(first_volume, out_dir, exclude, cancel, on_entry)
+                    .await
+            }
+        }
+    }
+
+    async fn extract_only(
+        &self,
+        first_volume: &std::path::Path,
+        out_dir: &std::path::Path,
+        paths: &[String],
+        cancel: &CancellationToken,
+        on_entry: impl FnMut(&str) -> Result<()> + Send + 'static,
+    ) -> Result<()> {
+        match self {
+            SevenZipConfig::Native => {
+                Native
+                    .extract_only(first_volume, out_dir, paths, cancel, on_entry)
+                    .await
+            }
+            SevenZipConfig::External { path } => {
+                Self::external(path)
+                    .extract_only(first_volume, out_dir, paths, cancel, on_entry)
+                    .await
+            }
+        }
+    }
+}
+
+pub fn configured_backend(app_handle: &tauri::AppHandle) -> Result<SevenZipConfig> {
+    let Some(config) = crate::settings::get(app_handle, "sevenzip_backend") else {
+        return Ok(SevenZipConfig::default());
+    };
+
+    Ok(serde_json::from_value(config)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `7z l -slt` of the first volume of a split archive.
+    static MULTI_VOLUME_LISTING: &str = "
+7-Zip (r) 23.01 (x64) : Copyright (c) 1999-2023 Igor Pavlov : 2023-06-20
+
+Scanning the drive for archives:
+1 file, 104857600 bytes (100 MiB)
+
+Listing archive: COEIROINK.7z.001
+
+--
+Path = COEIROINK.7z.001
+Type = Split
+Physical Size = 104857600
+Volumes = 3
+Total Physical Size = 262144000
+----
+Path = COEIROINK.7z
+Size = 262144000
+--
+Path = COEIROINK.7z
+Type = 7z
+Physical Size = 262144000
+Headers Size = 1234
+Method = LZMA2:24 BCJ
+Solid = +
+Blocks = 1
+
+----------
+Path = COEIROINK
+Size =\x20
+Packed Size = 0
+Modified = 2024-05-01 12:34:56.1234567
+Attributes = D
+CRC =
+Folder = +
+
+Path = COEIROINK/ speaker info .json\x20
+Size = 12
+Packed Size =\x20
+Modified = 2024-05-01 12:34:56.1234567
+Attributes = A
+CRC = 0A1B2C3D
+Folder = -
+
+Path = COEIROINK/engine
+Size = 0
+Packed Size = 0
+Modified =\x20
+Attributes = D
+CRC =\x20
+";
+
+    fn summary(entries: &[ArchiveEntry]) -> Vec<(&str, u64, bool, Option<u32>)> {
+        entries
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry.size, entry.is_dir, entry.crc))
+            .collect()
+    }
+
+    #[test]
+    fn parses_multi_volume_listing() {
+        let entries = parse_technical_listing(MULTI_VOLUME_LISTING).unwrap();
+
+        assert_eq!(
+            summary(&entries),
+            vec![
+                ("COEIROINK", 0, true, None),
+                (
+                    "COEIROINK/ speaker info .json ",
+                    12,
+                    false,
+                    Some(0x0A1B2C3D)
+                ),
+                ("COEIROINK/engine", 0, true, None),
+            ]
+        );
+        assert!(entries[0].modified.is_some());
+        assert!(entries[2].modified.is_none());
+    }
+
+    #[test]
+    fn parses_crlf_listing() {
+        let crlf = MULTI_VOLUME_LISTING.replace('\n', "\r\n");
+
+        assert_eq!(
+            summary(&parse_technical_listing(&crlf).unwrap()),
+            summary(&parse_technical_listing(MULTI_VOLUME_LISTING).unwrap())
+        );
+    }
+
+    #[test]
+    fn detects_directories() {
+        let cases = [
+            ("Folder = +", true),
+            ("Folder = -", false),
+            ("Attributes = D", true),
+            ("Attributes = D_ drwxr-xr-x", true),
+            ("Attributes = A", false),
+            ("Attributes = A_ -rw-r--r--", false),
+            ("", false),
+        ];
+        for (property, is_dir) in cases {
+            let listing = format!("----------\nPath = a\nSize = 0\n{}\n", property);
+            let entries = parse_technical_listing(&listing).unwrap();
+
+            assert_eq!(entries[0].is_dir, is_dir, "{:?}", property);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_listings() {
+        let cases = [
+            "Path = COEIROINK.7z\nType = 7z\n",
+            "----------\nSize = 1\n",
+            "----------\nPath = a\nSize = many\n",
+            "----------\nPath = a\nSize = 1\nCRC = XYZ\n",
+        ];
+        for listing in cases {
+            assert!(parse_technical_listing(listing).is_err(), "{:?}", listing);
+        }
+    }
+}
@@ -15,6 +15,27 @@ type DownloadInfo = {
 	os: "windows" | "mac";
 	version: string;
 	link: string;
+	releaseDate: string | null;
+	size: number | null;
+};
+
+type VolumeSpace = {
+	volume: string;
+	required: number;
+	available: number;
+};
+
+const toGib = (bytes: number) => (bytes / 1024 / 1024 / 1024).toFixed(1);
+
+type ReleaseChanges = {
+	installed: string | null;
+	target: string;
+	releases: {
+		version: string;
+		releaseDate: string | null;
+		notes: string;
+	}[];
+	downloads: DownloadInfo[];
 };
 
 const Configure: React.FC<{
@@ -58,11 +79,12 @@ const Configure: React.FC<{
 
 	useEffect(() => {
 		const getDownloadInfo = async () => {
-			const info = await invoke<DownloadInfo[]>("fetch_coeiroink_versions");
+			const info = await invoke<DownloadInfo[]>("fetch_coeiroink_versions", {
+				query: { os: "windows", majorVersion: 2 },
+			});
 
-			const windows = info.filter((v) => v.os === "windows");
-			setCoeiroinkVersions(windows);
-			const latest = windows[0];
+			setCoeiroinkVersions(info);
+			const latest = info[0];
 			if (!latest) return;
 			setCoeiroinkToInstall(`${latest.version}-cpu`);
 		};
@@ -96,6 +118,23 @@ const Configure: React.FC<{
 		})();
 	}, [coeiroinkToInstall, coeiroinkVersions, store]);
 
+	const [releaseChanges, setReleaseChanges] = useState<ReleaseChanges | null>(
+		null,
+	);
+
+	useEffect(() => {
+		(async () => {
+			setReleaseChanges(null);
+			if (!coeiroinkToInstall) return;
+
+			const [version] = coeiroinkToInstall.split("-");
+			const changes = await invoke<ReleaseChanges>("fetch_release_notes", {
+				version,
+			}).catch(() => null);
+			setReleaseChanges(changes);
+		})();
+	}, [coeiroinkToInstall]);
+
 	const [isSafe, setIsSafe] = useState(false);
 	const [checkedIsSafe, setCheckedIsSafe] = useState(false);
 
@@ -126,6 +165,32 @@ const Configure: React.FC<{
 		[installPath],
 	);
 
+	const [lackingSpace, setLackingSpace] = useState<VolumeSpace[]>([]);
+
+	useDebounce(
+		() => {
+			if (!coeiroinkToInstall || !installPath) {
+				setLackingSpace([]);
+				return;
+			}
+			invoke<VolumeSpace[]>("check_disk_space", {
+				params: {
+					edition: coeiroinkToInstall.split("-")[1],
+					version: coeiroinkToInstall.split("-")[0],
+					path: installPath,
+					desktopShortcut,
+					startMenuShortcut,
+				},
+			})
+				.then((volumes) =>
+					setLackingSpace(volumes.filter((v) => v.required > v.available)),
+				)
+				.catch(() => setLackingSpace([]));
+		},
+		1000,
+		[installPath, coeiroinkToInstall],
+	);
+
 	const submit = (e: React.FormEvent) => {
 		e.preventDefault();
 		if (!coeiroinkToInstall) return;
@@ -177,6 +242,19 @@ const Configure: React.FC<{
 						</Select.Content>
 					</Select.Portal>
 				</Select.Root>
+				{releaseChanges && releaseChanges.releases.length > 0 && (
+					<div className="text-xs max-h-24 overflow-y-auto">
+						{releaseChanges.releases.map((release) => (
+							<div key={release.version}>
+								<p className="font-bold">
+									v{release.version}
+									{release.releaseDate && `（${release.releaseDate}）`}
+								</p>
+								<p className="whitespace-pre-wrap">{release.notes}</p>
+							</div>
+						))}
+					</div>
+				)}
 			</section>
 			<section className="flex flex-col gap-2">
 				<h2>インストール先</h2>
@@ -211,6 +289,12 @@ const Configure: React.FC<{
 							: "このフォルダには他のファイルが存在します。"
 						: "フォルダを確認中..."}
 				</p>
+				{lackingSpace.map((space) => (
+					<p key={space.volume} className="text-xs text-accent">
+						{space.volume} の空き容量が不足しています（必要：
+						{toGib(space.required)}GiB、空き：{toGib(space.available)}GiB）
+					</p>
+				))}
 			</section>
 			<section className="flex flex-col gap-2">
 				<h2>ショートカット</h2>
@@ -259,7 +343,14 @@ const Configure: React.FC<{
 				<button
 					type="submit"
 					className="button"
-					disabled={!(coeiroinkToInstall && checkedIsSafe && isSafe)}
+					disabled={
+						!(
+							coeiroinkToInstall &&
+							checkedIsSafe &&
+							isSafe &&
+							lackingSpace.length === 0
+						)
+					}
 				>
 					インストール
 				</button>
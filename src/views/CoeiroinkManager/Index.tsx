@@ -6,6 +6,8 @@ import { Store } from "@/store.ts";
 import * as Toast from "@radix-ui/react-toast";
 import { useDebounce } from "react-use";
 import clsx from "clsx";
+import Uninstall from "./Uninstall.tsx";
+import Verify from "./Verify.tsx";
 
 type CoeiroinkVersion = {
 	version: string;
@@ -79,6 +81,7 @@ const Index: React.FC<{
 	})();
 
 	const [coeiroinkPath, setCoeiroinkPath] = useState("");
+	const [registeredPath, setRegisteredPath] = useState("");
 	const [isCoeiroinkDir, setIsCoeiroinkDir] = useState(false);
 	const [checkedIsCoeiroinkDir, setCheckedIsCoeiroinkDir] = useState(false);
 
@@ -88,9 +91,23 @@ const Index: React.FC<{
 			const path = await store.get<string | null>("coeiroink_root");
 
 			setCoeiroinkPath(path ?? "");
+			setRegisteredPath(path ?? "");
 		};
 
 		getCoeiroinkPath();
+
+		// The backend also changes coeiroink_root, e.g. when uninstalling.
+		const unlisten = store.onKeyChange<string | null>(
+			"coeiroink_root",
+			(path) => {
+				setCoeiroinkPath(path ?? "");
+				setRegisteredPath(path ?? "");
+			},
+		);
+
+		return () => {
+			unlisten.then((unlisten) => unlisten());
+		};
 	}, [store]);
 
 	useDebounce(
@@ -225,6 +242,14 @@ const Index: React.FC<{
 					<Toast.Viewport />
 				</Toast.Provider>
 			</section>
+			{coeiroinkVersion.isOk() &&
+				coeiroinkVersion.unwrap() &&
+				registeredPath && (
+					<>
+						<Verify path={registeredPath} />
+						<Uninstall path={registeredPath} />
+					</>
+				)}
 		</div>
 	);
 };
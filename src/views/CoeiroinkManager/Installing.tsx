@@ -5,6 +5,9 @@ import { useEffect, useRef, useState } from "react";
 import type { InstallContext } from "../CoeiroinkManager.tsx";
 
 type InstallProgress =
+	| {
+			type: "Queued";
+	  }
 	| {
 			type: "Initializing";
 	  }
@@ -12,6 +15,9 @@ type InstallProgress =
 			type: "Downloading";
 			progress: number;
 			total: number;
+			bytesPerSec: number;
+			etaSecs: number | null;
+			indeterminate: boolean;
 	  }
 	| {
 			type: "Extracting";
@@ -24,33 +30,73 @@ type InstallProgress =
 			total: number;
 			current: string;
 	  }
+	| {
+			type: "Removing";
+			progress: number;
+			total: number;
+	  }
+	| {
+			type: "Moving";
+			progress: number;
+			total: number;
+	  }
 	| {
 			type: "Configuring";
 	  }
 	| {
 			type: "Done";
+	  }
+	| {
+			type: "Cancelled";
 	  };
 
+type InstallEvent = InstallProgress & {
+	jobId: number;
+	percent: number;
+};
+
+type JobInfo = {
+	id: number;
+	status: "queued" | "running" | "succeeded" | "failed" | "cancelled";
+	error: string | null;
+	percent: number;
+	progress?: InstallProgress;
+};
+
 const typeToLevel = (type: InstallProgress["type"]) =>
 	[
-		"Initializing",
-		"Downloading",
-		"Extracting",
-		"Installing",
-		"Configuring",
-		"Done",
-	].indexOf(type);
+		["Queued", "Initializing"],
+		["Downloading"],
+		["Extracting"],
+		["Installing"],
+		["Removing", "Moving", "Configuring"],
+		["Done"],
+	].findIndex((types) => types.includes(type));
 
 const toMib = (bytes: number) => (bytes / 1024 / 1024).toFixed(2);
 
+const formatEta = (secs: number) =>
+	secs >= 3600
+		? `${Math.floor(secs / 3600)}時間${Math.floor((secs % 3600) / 60)}分`
+		: secs >= 60
+			? `${Math.floor(secs / 60)}分${secs % 60}秒`
+			: `${secs}秒`;
+
 const Installing: React.FC<{ context: InstallContext }> = ({ context }) => {
 	const invokedInstall = useRef(false);
 
 	const errorRef = useRef<string | null>(null);
-	const [status, setStatus] = useState<"working" | "done" | "error">("working");
-	const unlistenRef = useRef<(() => void) | null>(null);
-	const [installProgress, setInstallProgress] = useState<InstallProgress>({
+	const [status, setStatus] = useState<
+		"working" | "done" | "error" | "cancelled"
+	>("working");
+	const [cancelling, setCancelling] = useState(false);
+	const unlistenRef = useRef<(() => void)[]>([]);
+	const jobIdRef = useRef<number | null>(null);
+	const [jobId, setJobId] = useState<number | null>(null);
+	const [installProgress, setInstallProgress] = useState<InstallEvent>({
 		type: "Initializing",
+		jobId: -1,
+		percent: 0,
 	});
 
 	useEffect(() => {
@@ -58,23 +104,73 @@ const Installing: React.FC<{ context: InstallContext }> = ({ context }) => {
 			setStatus("working");
 			invokedInstall.current = true;
 
-			listen<InstallProgress>("installing_coeiroink", (data) => {
-				setInstallProgress(data.payload);
-				if (data.payload.type === "Done") {
+			const applyJob = (job: JobInfo) => {
+				if (job.status === "succeeded") {
 					setStatus("done");
 				}
-			}).then((unlisten) => {
-				unlistenRef.current = unlisten;
-			});
+				if (job.status === "cancelled") {
+					setStatus("cancelled");
+				}
+				if (job.status === "failed") {
+					errorRef.current = job.error;
+					setStatus("error");
+				}
+			};
+
+			const start = async () => {
+				// Only follow the job this install queued, not other running ones.
+				unlistenRef.current = await Promise.all([
+					listen<InstallEvent>("installing_coeiroink", (data) => {
+						if (data.payload.jobId !== jobIdRef.current) {
+							return;
+						}
+						setInstallProgress(data.payload);
+						if (data.payload.type === "Done") {
+							setStatus("done");
+						}
+						if (data.payload.type === "Cancelled") {
+							setStatus("cancelled");
+						}
+					}),
+					listen<JobInfo>("job_updated", (data) => {
+						if (data.payload.id === jobIdRef.current) {
+							applyJob(data.payload);
+						}
+					}),
+				]);
+
+				const id = await invoke<number>("install_coeiroink", {
+					params: context,
+				});
+				jobIdRef.current = id;
+				setJobId(id);
+
+				// Events sent before the ID was known were ignored; catch up with the job.
+				const job = await invoke<JobInfo | null>("get_job", { id });
+				if (!job) {
+					return;
+				}
+				const progress = job.progress;
+				if (progress) {
+					setInstallProgress((current) =>
+						current.jobId === id && current.percent > job.percent
+							? current
+							: { ...progress, jobId: id, percent: job.percent },
+					);
+				}
+				applyJob(job);
+			};
 
-			invoke("install_coeiroink", { params: context }).catch((e) => {
+			start().catch((e) => {
 				errorRef.current = String(e);
 				setStatus("error");
 			});
 		}
 
 		return () => {
-			unlistenRef.current?.();
+			for (const unlisten of unlistenRef.current) {
+				unlisten();
+			}
 		};
 	}, [context]);
 
@@ -88,7 +184,8 @@ const Installing: React.FC<{ context: InstallContext }> = ({ context }) => {
 		);
 
 	const cancel = () => {
-		invoke("cancel_install_coeiroink");
+		setCancelling(true);
+		invoke("cancel_job", { id: jobId });
 	};
 
 	return (
@@ -100,13 +197,25 @@ const Installing: React.FC<{ context: InstallContext }> = ({ context }) => {
 				インストール先：{context.path}
 			</p>
 			<ul>
-				<li className={getClasses(0)}>準備</li>
+				<li className={getClasses(0)}>
+					準備
+					{installProgress.type === "Queued" &&
+						"（他のインストールの完了を待っています）"}
+				</li>
 				<li className={getClasses(1)}>
 					ダウンロード
-					{installProgress.type === "Downloading" &&
-						`（${toMib(installProgress.progress)}MiB / ${toMib(
-							installProgress.total,
-						)}MiB）`}
+					{installProgress.type === "Downloading" && (
+						<>
+							（{toMib(installProgress.progress)}MiB /{" "}
+							{installProgress.indeterminate
+								? "？"
+								: `${toMib(installProgress.total)}MiB`}
+							、{toMib(installProgress.bytesPerSec)}MiB/s
+							{installProgress.etaSecs !== null &&
+								`、残り約${formatEta(installProgress.etaSecs)}`}
+							）
+						</>
+					)}
 				</li>
 				<li className={getClasses(2)}>
 					解凍
@@ -125,10 +234,28 @@ const Installing: React.FC<{ context: InstallContext }> = ({ context }) => {
 						</>
 					)}
 				</li>
-				<li className={getClasses(4)}>設定</li>
+				<li className={getClasses(4)}>
+					設定
+					{installProgress.type === "Removing" &&
+						`（古いファイルの削除：${installProgress.progress} / ${installProgress.total}）`}
+					{installProgress.type === "Moving" &&
+						`（ファイルの移動：${installProgress.progress} / ${installProgress.total}）`}
+				</li>
 				<li className={getClasses(5)}>完了</li>
 			</ul>
 
+			<div className="flex items-center gap-2">
+				<div className="nm-inset-coeiroink-sm rounded-md h-2 flex-grow overflow-hidden">
+					<div
+						className="bg-accent h-full transition-all"
+						style={{ width: `${installProgress.percent}%` }}
+					/>
+				</div>
+				<span className="text-sm w-12 text-right">
+					{Math.floor(installProgress.percent)}%
+				</span>
+			</div>
+
 			<div className="flex-grow" />
 
 			<div className="pt-4 flex flex-col gap-2">
@@ -137,8 +264,13 @@ const Installing: React.FC<{ context: InstallContext }> = ({ context }) => {
 						<p>
 							インストールには時間がかかります。お茶でも飲んでゆっくり待ちましょう。
 						</p>
-						<button type="button" onClick={cancel} className="button w-full">
-							キャンセル
+						<button
+							type="button"
+							onClick={cancel}
+							className="button w-full"
+							disabled={cancelling || jobId === null}
+						>
+							{cancelling ? "キャンセル中..." : "キャンセル"}
 						</button>
 					</>
 				)}
@@ -158,6 +290,18 @@ const Installing: React.FC<{ context: InstallContext }> = ({ context }) => {
 						</button>
 					</>
 				)}
+				{status === "cancelled" && (
+					<>
+						<p>インストールをキャンセルしました。</p>
+						<button
+							type="button"
+							onClick={() => window.location.reload()}
+							className="button w-full"
+						>
+							戻る
+						</button>
+					</>
+				)}
 				{status === "done" && (
 					<>
 						<p>インストールが完了しました。</p>
@@ -0,0 +1,179 @@
+import { useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { open as openDialog } from "@tauri-apps/plugin-dialog";
+import * as Select from "@radix-ui/react-select";
+import * as Checkbox from "@radix-ui/react-checkbox";
+import { CheckIcon, ChevronDownIcon } from "@radix-ui/react-icons";
+
+type SpeakerInfoAction =
+	| {
+			type: "keep";
+	  }
+	| {
+			type: "export";
+			path: string;
+	  }
+	| {
+			type: "delete";
+	  };
+
+type UninstallResult = {
+	installDir: string;
+	freedBytes: number;
+	removedShortcuts: string[];
+	exportedSpeakerInfo: string | null;
+};
+
+const toGib = (bytes: number) => (bytes / 1024 / 1024 / 1024).toFixed(1);
+
+const Uninstall: React.FC<{ path: string }> = ({ path }) => {
+	const [speakerInfo, setSpeakerInfo] =
+		useState<SpeakerInfoAction["type"]>("keep");
+	const [exportDir, setExportDir] = useState("");
+	const [removeBackups, setRemoveBackups] = useState(false);
+	const [status, setStatus] = useState<
+		| { type: "idle" }
+		| { type: "working" }
+		| { type: "done"; result: UninstallResult }
+		| { type: "error"; error: string }
+	>({ type: "idle" });
+
+	const browseExportDir = async () => {
+		const result = await openDialog({
+			directory: true,
+		});
+
+		if (result) {
+			setExportDir(result);
+		}
+	};
+
+	const uninstall = async () => {
+		const action: SpeakerInfoAction =
+			speakerInfo === "export"
+				? { type: "export", path: `${exportDir}\\speaker_info` }
+				: { type: speakerInfo };
+
+		setStatus({ type: "working" });
+		try {
+			const result = await invoke<UninstallResult>("uninstall_coeiroink", {
+				params: { path, speakerInfo: action, removeBackups },
+			});
+			setStatus({ type: "done", result });
+		} catch (e) {
+			setStatus({ type: "error", error: String(e) });
+		}
+	};
+
+	if (status.type === "done") {
+		return (
+			<section className="flex flex-col gap-2">
+				<h1>Coeiroinkをアンインストールしました</h1>
+				<p>
+					{toGib(status.result.freedBytes)}GiBの空き容量が増えました。
+					{status.result.exportedSpeakerInfo && (
+						<>
+							<br />
+							speaker_infoを{status.result.exportedSpeakerInfo}
+							に書き出しました。
+						</>
+					)}
+				</p>
+				<button
+					type="button"
+					className="button"
+					onClick={() => window.location.reload()}
+				>
+					戻る
+				</button>
+			</section>
+		);
+	}
+
+	return (
+		<section className="flex flex-col gap-2">
+			<h1>Coeiroinkをアンインストールする</h1>
+			<p>
+				{path}
+				を削除し、このフォルダを指すショートカットを削除します。
+			</p>
+			<Select.Root
+				value={speakerInfo}
+				onValueChange={(value) =>
+					setSpeakerInfo(value as SpeakerInfoAction["type"])
+				}
+			>
+				<Select.Trigger className="select-trigger">
+					<Select.Value />
+					<Select.Icon className="select-icon">
+						<ChevronDownIcon />
+					</Select.Icon>
+				</Select.Trigger>
+				<Select.Portal>
+					<Select.Content className="select-content">
+						<Select.Viewport className="select">
+							<Select.Item value="keep" className="select-item">
+								<Select.ItemText>speaker_infoを残す</Select.ItemText>
+							</Select.Item>
+							<Select.Item value="export" className="select-item">
+								<Select.ItemText>speaker_infoを書き出す</Select.ItemText>
+							</Select.Item>
+							<Select.Item value="delete" className="select-item">
+								<Select.ItemText>speaker_infoも削除する</Select.ItemText>
+							</Select.Item>
+						</Select.Viewport>
+						<Select.Arrow />
+					</Select.Content>
+				</Select.Portal>
+			</Select.Root>
+			{speakerInfo === "export" && (
+				<div className="flex gap-2">
+					<input
+						type="text"
+						className="input flex-grow"
+						value={exportDir}
+						onChange={(e) => setExportDir(e.target.value)}
+					/>
+					<button type="button" className="button" onClick={browseExportDir}>
+						参照
+					</button>
+				</div>
+			)}
+			<div className="flex">
+				<Checkbox.Root
+					className="checkbox-root"
+					checked={removeBackups}
+					onCheckedChange={(e) => setRemoveBackups(e.valueOf() as boolean)}
+					id="uninstall--remove-backups"
+				>
+					<Checkbox.Indicator className="checkbox-indicator">
+						<CheckIcon />
+					</Checkbox.Indicator>
+				</Checkbox.Root>
+				<label className="checkbox-label" htmlFor="uninstall--remove-backups">
+					以前のバージョンのバックアップも削除する
+				</label>
+			</div>
+			{status.type === "error" && (
+				<p className="text-accent text-xs">
+					エラーが発生しました：{status.error}
+				</p>
+			)}
+			<button
+				type="button"
+				className="button"
+				disabled={
+					status.type === "working" ||
+					(speakerInfo === "export" && exportDir === "")
+				}
+				onClick={uninstall}
+			>
+				{status.type === "working"
+					? "アンインストール中..."
+					: "アンインストールする"}
+			</button>
+		</section>
+	);
+};
+
+export default Uninstall;
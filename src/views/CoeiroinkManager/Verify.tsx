@@ -0,0 +1,133 @@
+import { useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import * as Checkbox from "@radix-ui/react-checkbox";
+import { CheckIcon } from "@radix-ui/react-icons";
+
+type VerifyReport = {
+	installDir: string;
+	version: string;
+	edition: "cpu" | "gpu";
+	checked: number;
+	missing: string[];
+	modified: string[];
+	extra: string[];
+	repaired: string[];
+};
+
+const FileList: React.FC<{ title: string; files: string[] }> = ({
+	title,
+	files,
+}) =>
+	files.length > 0 ? (
+		<div className="text-xs">
+			<p className="font-bold">
+				{title}（{files.length}）
+			</p>
+			<div className="max-h-24 overflow-y-auto break-all">
+				{files.map((file) => (
+					<p key={file}>{file}</p>
+				))}
+			</div>
+		</div>
+	) : null;
+
+const Verify: React.FC<{ path: string }> = ({ path }) => {
+	const [thorough, setThorough] = useState(false);
+	const [status, setStatus] = useState<
+		| { type: "idle" }
+		| { type: "working"; repair: boolean }
+		| { type: "done"; report: VerifyReport }
+		| { type: "error"; error: string }
+	>({ type: "idle" });
+
+	const verify = async (repair: boolean) => {
+		setStatus({ type: "working", repair });
+		try {
+			const report = await invoke<VerifyReport>("verify_coeiroink", {
+				params: { path, thorough, repair },
+			});
+			setStatus({ type: "done", report });
+		} catch (e) {
+			setStatus({ type: "error", error: String(e) });
+		}
+	};
+
+	const report = status.type === "done" ? status.report : null;
+	const broken = report
+		? report.missing.length + report.modified.length - report.repaired.length
+		: 0;
+
+	return (
+		<section className="flex flex-col gap-2">
+			<h1>Coeiroinkを検査する</h1>
+			<p>
+				インストールされたファイルをアーカイブと比較し、足りないファイルや壊れたファイルを修復します。アーカイブがキャッシュされていない場合はダウンロードします。
+			</p>
+			<div className="flex">
+				<Checkbox.Root
+					className="checkbox-root"
+					checked={thorough}
+					onCheckedChange={(e) => setThorough(e.valueOf() as boolean)}
+					id="verify--thorough"
+				>
+					<Checkbox.Indicator className="checkbox-indicator">
+						<CheckIcon />
+					</Checkbox.Indicator>
+				</Checkbox.Root>
+				<label className="checkbox-label" htmlFor="verify--thorough">
+					すべてのファイルの内容を確認する（時間がかかります）
+				</label>
+			</div>
+			{report && (
+				<>
+					<p>
+						{report.checked}個のファイルを検査しました。
+						{report.repaired.length > 0 &&
+							`${report.repaired.length}個のファイルを修復しました。`}
+						{broken === 0 &&
+							report.repaired.length === 0 &&
+							"問題はありません。"}
+					</p>
+					{report.repaired.length === 0 && (
+						<>
+							<FileList title="見つからないファイル" files={report.missing} />
+							<FileList title="変更されたファイル" files={report.modified} />
+						</>
+					)}
+					<FileList title="アーカイブにないファイル" files={report.extra} />
+				</>
+			)}
+			{status.type === "error" && (
+				<p className="text-accent text-xs">
+					エラーが発生しました：{status.error}
+				</p>
+			)}
+			<div className="grid gap-2 grid-cols-2">
+				<button
+					type="button"
+					className="button"
+					disabled={status.type === "working"}
+					onClick={() => verify(false)}
+				>
+					{status.type === "working" && !status.repair
+						? "検査中..."
+						: "検査する"}
+				</button>
+				<button
+					type="button"
+					className="button"
+					disabled={
+						status.type === "working" || (report !== null && broken === 0)
+					}
+					onClick={() => verify(true)}
+				>
+					{status.type === "working" && status.repair
+						? "修復中..."
+						: "修復する"}
+				</button>
+			</div>
+		</section>
+	);
+};
+
+export default Verify;